@@ -0,0 +1,148 @@
+use std::f32::consts::TAU;
+
+use bevy::prelude::*;
+
+/// Flight times tried when looking for the earliest one a projectile can make.
+const AIM_STEPS: u32 = 64;
+
+/// Halvings used to narrow down the flight time once it is bracketed.
+const AIM_REFINEMENTS: u32 = 16;
+
+/// Below this linear damping the closed form for undamped flight is used, which avoids dividing by zero.
+const MIN_DAMPING: f32 = 1.0e-4;
+
+/// Picks a random direction inside the cone of half-angle `spread` around the `-Z` axis of `aim`.
+pub fn scatter(aim: Quat, spread: f32) -> Dir3 {
+    // Taking the square root spreads the shots evenly over the cone instead of bunching them in the middle.
+    let angle = spread * rand::random::<f32>().sqrt();
+    let around = rand::random::<f32>() * TAU;
+    let offset = Quat::from_rotation_z(around) * Quat::from_rotation_x(angle);
+    Dir3::new_unchecked((aim * offset * Vec3::NEG_Z).normalize())
+}
+
+/// Finds the velocity to launch a projectile with from `origin`, so that it meets a target
+/// at `target` moving with `target_velocity`.
+///
+/// The projectile leaves with `speed` and flies under `gravity` and `linear_damping`,
+/// the way a dynamic Rapier body with `Damping` does. The earliest meeting within `max_time`
+/// seconds is picked, which is the direct shot rather than the lob. Returns `None` if the
+/// target can't be reached in time.
+pub fn solve_ballistic_aim(
+    origin: Vec3,
+    target: Vec3,
+    target_velocity: Vec3,
+    speed: f32,
+    gravity: Vec3,
+    linear_damping: f32,
+    max_time: f32,
+) -> Option<Vec3> {
+    // With damping k, a projectile launched with v travels v * (1 - e^(-kt)) / k after t seconds,
+    // and gravity pulls it g * (t - (1 - e^(-kt)) / k) / k off that line.
+    let launch_velocity = |time: f32| {
+        let (travel, fall) = if linear_damping < MIN_DAMPING {
+            (time, 0.5 * time * time)
+        } else {
+            let travel = (1.0 - (-linear_damping * time).exp()) / linear_damping;
+            (travel, (time - travel) / linear_damping)
+        };
+        (target + target_velocity * time - origin - gravity * fall) / travel
+    };
+    let too_slow = |time: f32| launch_velocity(time).length() > speed;
+
+    // Hitting early needs a fast projectile, so step forward until `speed` is enough,
+    // then narrow down the moment it first becomes enough.
+    let mut earlier = 0.0;
+    for step in 1..=AIM_STEPS {
+        let later = max_time * step as f32 / AIM_STEPS as f32;
+        if too_slow(later) {
+            earlier = later;
+            continue;
+        }
+
+        let (mut low, mut high) = (earlier, later);
+        for _ in 0..AIM_REFINEMENTS {
+            let middle = (low + high) / 2.0;
+            if too_slow(middle) {
+                low = middle;
+            } else {
+                high = middle;
+            }
+        }
+        return Some(launch_velocity(high).normalize_or_zero() * speed);
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Flies a projectile in small steps and returns how close it gets to the moving target.
+    fn closest_approach(
+        origin: Vec3,
+        velocity: Vec3,
+        target: Vec3,
+        target_velocity: Vec3,
+        gravity: Vec3,
+        linear_damping: f32,
+        max_time: f32,
+    ) -> f32 {
+        let step = 1.0e-4;
+        let (mut position, mut velocity) = (origin, velocity);
+        let mut closest = f32::INFINITY;
+        let mut time = 0.0;
+        while time < max_time {
+            closest = closest.min(position.distance(target + target_velocity * time));
+            velocity += (gravity - linear_damping * velocity) * step;
+            position += velocity * step;
+            time += step;
+        }
+        closest
+    }
+
+    #[test]
+    fn hits_the_target_under_gravity_and_damping() {
+        let origin = Vec3::new(0.0, 1.0, 0.0);
+        let target = Vec3::new(12.0, 0.5, -6.0);
+        let target_velocity = Vec3::new(0.0, 0.0, 2.0);
+        let gravity = Vec3::new(0.0, -9.81, 0.0);
+        let (speed, damping, max_time) = (15.0, 0.5, 5.0);
+
+        let velocity = solve_ballistic_aim(
+            origin,
+            target,
+            target_velocity,
+            speed,
+            gravity,
+            damping,
+            max_time,
+        )
+        .expect("the target is in reach");
+
+        assert!((velocity.length() - speed).abs() < 1.0e-3);
+        let miss = closest_approach(
+            origin,
+            velocity,
+            target,
+            target_velocity,
+            gravity,
+            damping,
+            max_time,
+        );
+        assert!(miss < 0.1, "missed by {miss} meters");
+    }
+
+    #[test]
+    fn gives_up_on_targets_out_of_reach() {
+        let aim = solve_ballistic_aim(
+            Vec3::ZERO,
+            Vec3::new(500.0, 0.0, 0.0),
+            Vec3::ZERO,
+            15.0,
+            Vec3::new(0.0, -9.81, 0.0),
+            0.5,
+            5.0,
+        );
+        assert_eq!(aim, None);
+    }
+}
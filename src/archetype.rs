@@ -0,0 +1,335 @@
+use std::fmt;
+use std::time::Duration;
+
+use bevy::asset::io::Reader;
+use bevy::asset::{AssetLoader, LoadContext, LoadedFolder};
+use bevy::pbr::StandardMaterial;
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+use serde::Deserialize;
+
+use crate::behavior::{Behavior, BehaviorTree};
+use crate::enemy::{Difficulty, Dying, Enemy};
+use crate::entity::Health;
+use crate::perception::Perception;
+
+/// Folder inside the assets folder the enemy archetypes are loaded from.
+const ARCHETYPE_FOLDER: &str = "enemies";
+
+/// One kind of enemy, like a grunt or a sniper, loaded from an `.enemy.ron` file.
+///
+/// Editing the file while the game runs updates every enemy of that kind.
+#[derive(Debug, Clone, Asset, TypePath, Deserialize)]
+pub struct EnemyArchetype {
+    pub name: String,
+    /// First wave enemies of this kind show up in.
+    pub first_wave: u32,
+    pub health: i32,
+    /// Meters per second. Enemies without any speed stay where they spawned, like a turret.
+    pub speed: f32,
+    /// Edge length of the cube, in meters.
+    pub size: f32,
+    pub color: Color,
+    /// Seconds between picking new spots to walk to while patrolling or searching.
+    pub move_interval: f32,
+    /// Seconds between shots.
+    pub shoot_interval: f32,
+    /// Shots fired before having to reload.
+    pub magazine_size: u32,
+    /// Seconds a reload takes, which the enemy spends in cover.
+    pub reload_time: f32,
+    /// See `Enemy::attack_range`.
+    pub attack_range: f32,
+    /// See `Enemy::flee_below`.
+    pub flee_below: f32,
+    /// See `Enemy::accuracy`.
+    pub accuracy: f32,
+    pub projectile_speed: f32,
+    pub projectile_damage: i32,
+    pub projectile_knockback: f32,
+    pub projectile_color: Color,
+    #[serde(default)]
+    pub perception: Perception,
+    /// Path of the behavior tree driving this kind, relative to the assets folder.
+    /// Kinds without one run the built-in state machine of `enemy_ai`.
+    #[serde(default)]
+    pub behavior: Option<String>,
+    /// The tree at `behavior`, loaded along with the archetype.
+    #[serde(skip)]
+    pub behavior_tree: Option<Handle<BehaviorTree>>,
+}
+
+impl EnemyArchetype {
+    pub fn max_health(&self, difficulty: &Difficulty) -> i32 {
+        (self.health as f32 * difficulty.health).round().max(1.0) as i32
+    }
+
+    /// Overwrites the stats of `enemy` with the ones of this kind, keeping its timers running.
+    pub fn apply(&self, enemy: &mut Enemy, difficulty: &Difficulty) {
+        enemy
+            .move_timer
+            .set_duration(Duration::from_secs_f32(self.move_interval));
+        enemy
+            .shoot_timer
+            .set_duration(Duration::from_secs_f32(self.shoot_interval));
+        enemy
+            .reload_timer
+            .set_duration(Duration::from_secs_f32(self.reload_time));
+        enemy.magazine_size = self.magazine_size;
+        enemy.magazine = enemy.magazine.min(self.magazine_size);
+        enemy.speed = self.speed * difficulty.speed;
+        enemy.attack_range = self.attack_range;
+        enemy.flee_below = self.flee_below;
+        enemy.accuracy = (self.accuracy + difficulty.accuracy).min(1.0);
+        enemy.projectile_speed = self.projectile_speed;
+        enemy.projectile_damage = self.projectile_damage;
+        enemy.projectile_knockback = self.projectile_knockback;
+        enemy.projectile_color = self.projectile_color;
+    }
+
+    /// Checks for stats that would crash the game or leave the enemy stuck.
+    fn validate(&self) -> Result<(), RonAssetError> {
+        let invalid = |reason: String| Err(RonAssetError::Invalid(reason));
+        for (field, seconds) in [
+            ("move_interval", self.move_interval),
+            ("shoot_interval", self.shoot_interval),
+            ("reload_time", self.reload_time),
+        ] {
+            if !(seconds.is_finite() && seconds >= 0.0) {
+                return invalid(format!(
+                    "`{field}` of {} must be zero or more seconds, but is {seconds}",
+                    self.name
+                ));
+            }
+        }
+        if self.magazine_size == 0 {
+            return invalid(format!(
+                "`magazine_size` of {} must be at least 1",
+                self.name
+            ));
+        }
+        if !(self.size.is_finite() && self.size > 0.0) {
+            return invalid(format!(
+                "`size` of {} must be more than zero meters, but is {}",
+                self.name, self.size
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn collider(&self) -> Collider {
+        Collider::cuboid(self.size / 2.0, self.size / 2.0, self.size / 2.0)
+    }
+
+    pub fn mesh(&self) -> Mesh {
+        Cuboid::from_length(self.size).into()
+    }
+}
+
+/// The archetype an enemy was spawned from.
+#[derive(Debug, Clone, Component)]
+pub struct Archetype(pub Handle<EnemyArchetype>);
+
+/// Why reading one of the RON assets, like an archetype or a behavior tree, failed.
+#[derive(Debug)]
+pub enum RonAssetError {
+    Io(std::io::Error),
+    Ron(ron::error::SpannedError),
+    /// Parsed, but doesn't make sense.
+    Invalid(String),
+}
+
+impl fmt::Display for RonAssetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(error) => write!(f, "could not read asset: {error}"),
+            Self::Ron(error) => write!(f, "could not parse asset: {error}"),
+            Self::Invalid(reason) => write!(f, "invalid asset: {reason}"),
+        }
+    }
+}
+
+impl std::error::Error for RonAssetError {}
+
+impl From<std::io::Error> for RonAssetError {
+    fn from(error: std::io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
+impl From<ron::error::SpannedError> for RonAssetError {
+    fn from(error: ron::error::SpannedError) -> Self {
+        Self::Ron(error)
+    }
+}
+
+/// Reads `EnemyArchetype`s from RON files ending in `.enemy.ron`.
+#[derive(Default)]
+pub struct EnemyArchetypeLoader;
+
+impl AssetLoader for EnemyArchetypeLoader {
+    type Asset = EnemyArchetype;
+    type Settings = ();
+    type Error = RonAssetError;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        _settings: &(),
+        load_context: &mut LoadContext<'_>,
+    ) -> Result<EnemyArchetype, Self::Error> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        let mut archetype: EnemyArchetype = ron::de::from_bytes(&bytes)?;
+        archetype.validate()?;
+        archetype.behavior_tree = archetype
+            .behavior
+            .as_deref()
+            .map(|path| load_context.load(path));
+        Ok(archetype)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["enemy.ron"]
+    }
+}
+
+/// Every archetype in the enemies folder of the assets.
+#[derive(Debug, Resource)]
+pub struct EnemyArchetypes {
+    folder: Handle<LoadedFolder>,
+}
+
+impl EnemyArchetypes {
+    /// The archetypes that finished loading, with their handles.
+    pub fn loaded<'a>(
+        &'a self,
+        folders: &'a Assets<LoadedFolder>,
+        archetypes: &'a Assets<EnemyArchetype>,
+    ) -> impl Iterator<Item = (Handle<EnemyArchetype>, &'a EnemyArchetype)> + 'a {
+        folders
+            .get(&self.folder)
+            .into_iter()
+            .flat_map(|folder| &folder.handles)
+            .filter_map(|handle| handle.clone().try_typed::<EnemyArchetype>().ok())
+            .filter_map(|handle| {
+                let archetype = archetypes.get(&handle)?;
+                Some((handle, archetype))
+            })
+    }
+}
+
+/// Starts loading the enemy archetypes.
+pub fn load_archetypes(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(EnemyArchetypes {
+        folder: asset_server.load_folder(ARCHETYPE_FOLDER),
+    });
+}
+
+/// Applies changes to archetype files to the enemies already spawned from them.
+pub fn reload_archetypes(
+    mut commands: Commands,
+    mut asset_events: EventReader<AssetEvent<EnemyArchetype>>,
+    archetypes: Res<Assets<EnemyArchetype>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut enemy_query: Query<
+        (
+            Entity,
+            &Archetype,
+            &Difficulty,
+            &mut Enemy,
+            &mut Perception,
+            &mut Health,
+            &mut Transform,
+            &mut Collider,
+            &mut Mesh3d,
+            &mut MeshMaterial3d<StandardMaterial>,
+        ),
+        Without<Dying>,
+    >,
+) {
+    for event in asset_events.read() {
+        let AssetEvent::Modified { id } = event else {
+            continue;
+        };
+        let Some(archetype) = archetypes.get(*id) else {
+            continue;
+        };
+        info!("Reloaded enemy archetype {}", archetype.name);
+
+        // All enemies of a kind share their assets, just like freshly spawned ones would.
+        let mesh = meshes.add(archetype.mesh());
+        let material = materials.add(archetype.color);
+
+        for (
+            entity,
+            Archetype(handle),
+            difficulty,
+            mut enemy,
+            mut perception,
+            mut health,
+            mut transform,
+            mut collider,
+            mut mesh_3d,
+            mut mesh_material,
+        ) in &mut enemy_query
+        {
+            if handle.id() != *id {
+                continue;
+            }
+
+            archetype.apply(&mut enemy, difficulty);
+            *perception = archetype.perception.clone();
+
+            health.max = archetype.max_health(difficulty);
+            health.current = health.current.min(health.max);
+
+            // Keep the bottom of the cube on the floor when it changes size.
+            if let Some(cuboid) = collider.as_cuboid() {
+                let old_half_size = cuboid.half_extents().y;
+                transform.translation.y += archetype.size / 2.0 - old_half_size;
+            }
+            *collider = archetype.collider();
+            mesh_3d.0 = mesh.clone();
+            mesh_material.0 = material.clone();
+
+            // Swapping the tree starts it over, just like for a fresh enemy.
+            match &archetype.behavior_tree {
+                Some(tree) => commands.entity(entity).insert(Behavior::new(tree.clone())),
+                None => commands.entity(entity).remove::<Behavior>(),
+            };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grunt() -> EnemyArchetype {
+        ron::de::from_str(include_str!("../assets/enemies/grunt.enemy.ron"))
+            .expect("the grunt parses")
+    }
+
+    #[test]
+    fn accepts_the_grunt() {
+        assert!(grunt().validate().is_ok());
+    }
+
+    #[test]
+    fn rejects_stats_that_break_enemies() {
+        let broken: [fn(&mut EnemyArchetype); 5] = [
+            |archetype| archetype.move_interval = -1.0,
+            |archetype| archetype.shoot_interval = f32::NAN,
+            |archetype| archetype.reload_time = f32::INFINITY,
+            |archetype| archetype.magazine_size = 0,
+            |archetype| archetype.size = 0.0,
+        ];
+        for breaking in broken {
+            let mut archetype = grunt();
+            breaking(&mut archetype);
+            assert!(archetype.validate().is_err(), "{archetype:?} was accepted");
+        }
+    }
+}
@@ -0,0 +1,813 @@
+use std::time::Duration;
+
+use bevy::asset::io::Reader;
+use bevy::asset::{AssetLoader, LoadContext};
+use bevy::pbr::StandardMaterial;
+use bevy::platform::collections::HashSet;
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+use serde::Deserialize;
+
+use crate::archetype::RonAssetError;
+use crate::cover::{CoverPoints, CoverSettings};
+use crate::damage::{DamageRules, Knockback};
+use crate::enemy::{Dying, Enemy, EnemyShot, drive_enemy, fire_at_player};
+use crate::entity::{Health, Player, Team};
+use crate::movement::PlayerVelocity;
+use crate::navigation::{NavAgent, NavGrid};
+use crate::perception::Awareness;
+use crate::squad::{Squad, SquadSettings};
+
+/// Most children a `Parallel` node can have, one for every bit of `NodeMemory::succeeded`.
+const MAX_PARALLEL_CHILDREN: usize = u64::BITS as usize;
+
+/// Outcome of ticking a node of a behavior tree.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    Success,
+    Failure,
+    /// Not done yet, tick it again next frame.
+    Running,
+}
+
+/// When a `Parallel` node is done.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum ParallelPolicy {
+    /// Succeeds once every child succeeded, fails as soon as one fails.
+    RequireAll,
+    /// Succeeds as soon as one child succeeds, fails once every child failed.
+    RequireOne,
+}
+
+/// Something about the enemy or the player a tree can check.
+#[derive(Debug, Clone, Deserialize)]
+pub enum Condition {
+    SeesPlayer,
+    /// Sees the player, or still remembers where they were.
+    KnowsPlayer,
+    /// The last known position of the player is within this many meters.
+    PlayerWithin(f32),
+    /// Health is below this fraction of the maximum.
+    HealthBelow(f32),
+    Reloading,
+    /// Took damage a moment ago.
+    Hurt,
+}
+
+/// A place an action can head for or look at.
+#[derive(Debug, Clone, Deserialize)]
+pub enum Target {
+    /// Where the player was last seen or heard.
+    Player,
+    /// A random spot within this many meters, picked when the action starts.
+    Random(f32),
+    /// The closest spot out of the player's sight, see `CoverPoints`.
+    Cover,
+    /// This many meters further away from the player, picked when the action starts.
+    AwayFromPlayer(f32),
+}
+
+fn full_speed() -> f32 {
+    1.0
+}
+
+/// A node of a behavior tree, as written in a `.bt.ron` file.
+#[derive(Debug, Clone, Deserialize)]
+pub enum BehaviorNode {
+    /// Runs its children one after the other, failing as soon as one fails.
+    Sequence(Vec<BehaviorNode>),
+    /// Runs the first child that doesn't fail. The children are checked in order every
+    /// frame, so a higher one can take over from a lower one that is running.
+    Selector(Vec<BehaviorNode>),
+    /// Runs all children every frame until `policy` decides. Takes up to 64 children,
+    /// trees with more don't load.
+    Parallel {
+        policy: ParallelPolicy,
+        children: Vec<BehaviorNode>,
+    },
+    /// Turns the success of its child into failure and the other way round.
+    Invert(Box<BehaviorNode>),
+    /// Succeeds once its child is done, no matter how it went.
+    Succeed(Box<BehaviorNode>),
+    /// Runs its child again every time it succeeds, `times` times or forever without a count.
+    /// Fails as soon as the child fails.
+    Repeat {
+        times: Option<u32>,
+        child: Box<BehaviorNode>,
+    },
+    /// Fails if its child takes longer than `seconds`.
+    Timeout {
+        seconds: f32,
+        child: Box<BehaviorNode>,
+    },
+    /// Runs its child for as long as `condition` holds, and fails once it doesn't.
+    While {
+        condition: Condition,
+        child: Box<BehaviorNode>,
+    },
+    /// Succeeds if the condition holds and fails otherwise.
+    Check(Condition),
+    /// Walks to `target` at `speed` times the speed of the enemy, succeeding on arrival
+    /// and failing if there is no way there.
+    MoveTo {
+        target: Target,
+        #[serde(default = "full_speed")]
+        speed: f32,
+    },
+    /// Faces the player in sight and succeeds once it fired a shot.
+    /// Fails when the player is out of sight or the magazine is empty.
+    ShootAt,
+    /// Succeeds after this many seconds.
+    Wait(f32),
+    /// Turns to face `target` and succeeds.
+    LookAt(Target),
+}
+
+/// What a node remembers between frames. Every node gets one, in depth-first order.
+#[derive(Debug, Default, Clone)]
+struct NodeMemory {
+    /// Child a `Sequence` or `Selector` is at.
+    child: usize,
+    /// Children of a `Parallel` that are done, one bit each.
+    succeeded: u64,
+    failed: u64,
+    /// Successes of a `Repeat`.
+    count: u32,
+    /// Seconds a `Wait` or `Timeout` has been running.
+    elapsed: f32,
+    /// Spot picked when a `MoveTo` or `LookAt` started.
+    target: Option<Vec3>,
+}
+
+/// The enemy a tree is ticked for, with everything its leaves can look at or change.
+struct Actor<'a> {
+    entity: Entity,
+    position: Vec3,
+    delta: Duration,
+    now: f32,
+    enemy: &'a mut Enemy,
+    agent: &'a mut NavAgent,
+    awareness: &'a Awareness,
+    health: &'a Health,
+    grid: Option<&'a NavGrid>,
+    squad: &'a mut Squad,
+    squad_settings: &'a SquadSettings,
+    cover: Option<&'a mut CoverPoints>,
+    cover_settings: &'a CoverSettings,
+    rapier: Option<&'a RapierContext<'a>>,
+    /// Set by `MoveTo`, the enemy stands still otherwise.
+    speed: Option<f32>,
+    /// Set by `ShootAt` and `LookAt`, the enemy looks where it goes otherwise.
+    facing: Vec3,
+    /// A `ShootAt` fired this frame.
+    fire: bool,
+    /// A `MoveTo` is using its claim on a cover point.
+    in_cover: bool,
+}
+
+impl Actor<'_> {
+    fn towards(&self, target: Vec3) -> Vec3 {
+        (target - self.position).with_y(0.0).normalize_or_zero()
+    }
+
+    fn holds(&self, condition: &Condition) -> bool {
+        match condition {
+            Condition::SeesPlayer => self.awareness.sees_player,
+            Condition::KnowsPlayer => self.awareness.last_known_position.is_some(),
+            Condition::PlayerWithin(distance) => self
+                .awareness
+                .last_known_position
+                .is_some_and(|player| player.xz().distance(self.position.xz()) <= *distance),
+            Condition::HealthBelow(fraction) => self.health.fraction() < *fraction,
+            Condition::Reloading => self.enemy.reloading(),
+            Condition::Hurt => self.enemy.shaken(),
+        }
+    }
+
+    /// Where `target` is, or `None` if it can't be made out.
+    fn locate(&mut self, target: &Target, memory: &mut NodeMemory) -> Option<Vec3> {
+        let player = self.awareness.last_known_position;
+        let position = self.position;
+        let spot = match target {
+            Target::Player => player?,
+            Target::Random(radius) => *memory.target.get_or_insert_with(|| {
+                let angle = rand::random::<f32>() * std::f32::consts::TAU;
+                let distance = rand::random::<f32>() * radius;
+                position + Vec3::new(angle.cos(), 0.0, angle.sin()) * distance
+            }),
+            Target::AwayFromPlayer(distance) => match memory.target {
+                Some(spot) => spot,
+                None => *memory
+                    .target
+                    .insert(position - self.towards(player?) * *distance),
+            },
+            Target::Cover => {
+                let spot = self.cover.as_deref_mut()?.claim(
+                    self.entity,
+                    position,
+                    player?,
+                    self.rapier?,
+                    self.cover_settings,
+                )?;
+                self.in_cover = true;
+                spot
+            }
+        };
+        Some(
+            self.grid
+                .and_then(|grid| grid.nearest_walkable(spot))
+                .unwrap_or(spot),
+        )
+    }
+}
+
+impl BehaviorNode {
+    fn children(&self) -> &[BehaviorNode] {
+        match self {
+            Self::Sequence(children)
+            | Self::Selector(children)
+            | Self::Parallel { children, .. } => children,
+            Self::Invert(child)
+            | Self::Succeed(child)
+            | Self::Repeat { child, .. }
+            | Self::Timeout { child, .. }
+            | Self::While { child, .. } => std::slice::from_ref(child),
+            _ => &[],
+        }
+    }
+
+    /// Checks the tree under this node for things that would break it when ticked.
+    fn validate(&self) -> Result<(), RonAssetError> {
+        if let Self::Parallel { children, .. } = self
+            && children.len() > MAX_PARALLEL_CHILDREN
+        {
+            return Err(RonAssetError::Invalid(format!(
+                "a `Parallel` node has {} children, but at most {MAX_PARALLEL_CHILDREN} are supported",
+                children.len()
+            )));
+        }
+        self.children().iter().try_for_each(Self::validate)
+    }
+
+    /// Number of nodes in the tree under this one, itself included.
+    fn size(&self) -> usize {
+        1 + self.children().iter().map(Self::size).sum::<usize>()
+    }
+
+    /// Ticks the node whose memory is at `index`, and forgets everything under it once it's done,
+    /// so it starts over the next time it runs.
+    fn tick(&self, index: usize, memory: &mut [NodeMemory], actor: &mut Actor) -> Status {
+        let status = self.run(index, memory, actor);
+        if status != Status::Running {
+            memory[index..index + self.size()].fill(NodeMemory::default());
+        }
+        status
+    }
+
+    fn run(&self, index: usize, memory: &mut [NodeMemory], actor: &mut Actor) -> Status {
+        let first_child = index + 1;
+        match self {
+            Self::Sequence(children) => {
+                while let Some(child) = children.get(memory[index].child) {
+                    let offset = first_child
+                        + children[..memory[index].child]
+                            .iter()
+                            .map(Self::size)
+                            .sum::<usize>();
+                    match child.tick(offset, memory, actor) {
+                        Status::Success => memory[index].child += 1,
+                        status => return status,
+                    }
+                }
+                Status::Success
+            }
+            Self::Selector(children) => {
+                let mut offset = first_child;
+                for (current, child) in children.iter().enumerate() {
+                    let status = child.tick(offset, memory, actor);
+                    if status != Status::Failure {
+                        // Taking over from a lower child that was still running starts that one over.
+                        let previous = memory[index].child;
+                        if previous > current {
+                            let start = first_child
+                                + children[..previous].iter().map(Self::size).sum::<usize>();
+                            memory[start..start + children[previous].size()]
+                                .fill(NodeMemory::default());
+                        }
+                        memory[index].child = current;
+                        return status;
+                    }
+                    offset += child.size();
+                }
+                Status::Failure
+            }
+            Self::Parallel { policy, children } => {
+                let mut offset = first_child;
+                for (current, child) in children.iter().enumerate() {
+                    let bit = 1 << current;
+                    let done = (memory[index].succeeded | memory[index].failed) & bit != 0;
+                    if !done {
+                        match child.tick(offset, memory, actor) {
+                            Status::Success => memory[index].succeeded |= bit,
+                            Status::Failure => memory[index].failed |= bit,
+                            Status::Running => {}
+                        }
+                    }
+                    offset += child.size();
+                }
+
+                let succeeded = memory[index].succeeded.count_ones() as usize;
+                let failed = memory[index].failed.count_ones() as usize;
+                match policy {
+                    ParallelPolicy::RequireAll if failed > 0 => Status::Failure,
+                    ParallelPolicy::RequireAll if succeeded == children.len() => Status::Success,
+                    ParallelPolicy::RequireOne if succeeded > 0 => Status::Success,
+                    ParallelPolicy::RequireOne if failed == children.len() => Status::Failure,
+                    _ => Status::Running,
+                }
+            }
+            Self::Invert(child) => match child.tick(first_child, memory, actor) {
+                Status::Success => Status::Failure,
+                Status::Failure => Status::Success,
+                Status::Running => Status::Running,
+            },
+            Self::Succeed(child) => match child.tick(first_child, memory, actor) {
+                Status::Running => Status::Running,
+                _ => Status::Success,
+            },
+            Self::Repeat { times, child } => match child.tick(first_child, memory, actor) {
+                Status::Success => {
+                    memory[index].count += 1;
+                    if times.is_some_and(|times| memory[index].count >= times) {
+                        Status::Success
+                    } else {
+                        Status::Running
+                    }
+                }
+                status => status,
+            },
+            Self::Timeout { seconds, child } => {
+                memory[index].elapsed += actor.delta.as_secs_f32();
+                if memory[index].elapsed > *seconds {
+                    return Status::Failure;
+                }
+                child.tick(first_child, memory, actor)
+            }
+            Self::While { condition, child } => {
+                if !actor.holds(condition) {
+                    return Status::Failure;
+                }
+                child.tick(first_child, memory, actor)
+            }
+            Self::Check(condition) => {
+                if actor.holds(condition) {
+                    Status::Success
+                } else {
+                    Status::Failure
+                }
+            }
+            Self::MoveTo { target, speed } => {
+                let Some(destination) = actor.locate(target, &mut memory[index]) else {
+                    return Status::Failure;
+                };
+                actor.agent.go_to(destination, actor.position, actor.grid);
+                actor.speed = Some(actor.enemy.speed * speed);
+                if actor.agent.reached(destination, actor.position) {
+                    Status::Success
+                } else if actor.agent.arrived() {
+                    // No way to get there.
+                    Status::Failure
+                } else {
+                    Status::Running
+                }
+            }
+            Self::ShootAt => {
+                let Some(player) = actor.awareness.last_known_position else {
+                    return Status::Failure;
+                };
+                if !actor.awareness.sees_player || actor.enemy.reloading() {
+                    return Status::Failure;
+                }
+                actor.facing = actor.towards(player);
+                actor.enemy.shoot_timer.tick(actor.delta);
+                if actor.enemy.ready_to_fire()
+                    && actor
+                        .squad
+                        .take_shot(actor.now, actor.squad_settings.shot_spacing)
+                {
+                    actor.fire = true;
+                    Status::Success
+                } else {
+                    Status::Running
+                }
+            }
+            Self::Wait(seconds) => {
+                memory[index].elapsed += actor.delta.as_secs_f32();
+                if memory[index].elapsed >= *seconds {
+                    Status::Success
+                } else {
+                    Status::Running
+                }
+            }
+            Self::LookAt(target) => {
+                let Some(spot) = actor.locate(target, &mut memory[index]) else {
+                    return Status::Failure;
+                };
+                actor.facing = actor.towards(spot);
+                Status::Success
+            }
+        }
+    }
+}
+
+/// A behavior tree loaded from a `.bt.ron` file.
+#[derive(Debug, Clone, Asset, TypePath, Deserialize)]
+pub struct BehaviorTree {
+    pub root: BehaviorNode,
+}
+
+/// Reads `BehaviorTree`s from RON files ending in `.bt.ron`.
+#[derive(Default)]
+pub struct BehaviorTreeLoader;
+
+impl AssetLoader for BehaviorTreeLoader {
+    type Asset = BehaviorTree;
+    type Settings = ();
+    type Error = RonAssetError;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        _settings: &(),
+        _load_context: &mut LoadContext<'_>,
+    ) -> Result<BehaviorTree, Self::Error> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        let tree: BehaviorTree = ron::de::from_bytes(&bytes)?;
+        tree.root.validate()?;
+        Ok(tree)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["bt.ron"]
+    }
+}
+
+/// Drives an enemy with a behavior tree instead of the state machine of `enemy_ai`.
+#[derive(Debug, Clone, Component)]
+pub struct Behavior {
+    pub tree: Handle<BehaviorTree>,
+    memory: Vec<NodeMemory>,
+}
+
+impl Behavior {
+    pub fn new(tree: Handle<BehaviorTree>) -> Self {
+        Self {
+            tree,
+            memory: Vec::new(),
+        }
+    }
+}
+
+/// Ticks the behavior tree of every enemy that has one, then moves and shoots the way its
+/// leaves asked for. A tree that is done starts over the next frame.
+pub fn run_behaviors(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    rules: Res<DamageRules>,
+    grid: Option<Res<NavGrid>>,
+    (squad_settings, mut squad): (Res<SquadSettings>, ResMut<Squad>),
+    (cover_settings, mut cover): (Res<CoverSettings>, Option<ResMut<CoverPoints>>),
+    rapier_context: ReadRapierContext,
+    trees: Res<Assets<BehaviorTree>>,
+    mut tree_events: EventReader<AssetEvent<BehaviorTree>>,
+    mut enemy_query: Query<
+        (
+            Entity,
+            &mut Behavior,
+            &mut Transform,
+            &mut Enemy,
+            &mut NavAgent,
+            &mut Knockback,
+            &mut KinematicCharacterController,
+            &Awareness,
+            &Health,
+            &Team,
+        ),
+        (Without<Player>, Without<Dying>),
+    >,
+    player_query: Query<(&Transform, &PlayerVelocity), With<Player>>,
+    physics: Query<&RapierConfiguration>,
+) {
+    let Ok((player_transform, player_velocity)) = player_query.single() else {
+        return;
+    };
+    let gravity = physics
+        .single()
+        .map_or(Vec3::NEG_Y * 9.81, |config| config.gravity);
+    let grid = grid.as_deref();
+    let rapier = rapier_context.single().ok();
+
+    // Memory laid out for the old shape of a tree means nothing for the new one.
+    let modified: HashSet<_> = tree_events
+        .read()
+        .filter_map(|event| match event {
+            AssetEvent::Modified { id } => Some(*id),
+            _ => None,
+        })
+        .collect();
+
+    for (
+        entity,
+        mut behavior,
+        mut transform,
+        mut enemy,
+        mut agent,
+        mut knockback,
+        mut controller,
+        awareness,
+        health,
+        &team,
+    ) in &mut enemy_query
+    {
+        // Still loading.
+        let Some(tree) = trees.get(&behavior.tree) else {
+            continue;
+        };
+        let size = tree.root.size();
+        if modified.contains(&behavior.tree.id()) || behavior.memory.len() != size {
+            behavior.memory = vec![NodeMemory::default(); size];
+        }
+
+        let position = transform.translation;
+        let mut actor = Actor {
+            entity,
+            position,
+            delta: time.delta(),
+            now: time.elapsed_secs(),
+            enemy: &mut enemy,
+            agent: &mut agent,
+            awareness,
+            health,
+            grid,
+            squad: &mut squad,
+            squad_settings: &squad_settings,
+            cover: cover.as_deref_mut(),
+            cover_settings: &cover_settings,
+            rapier: rapier.as_ref(),
+            speed: None,
+            facing: Vec3::ZERO,
+            fire: false,
+            in_cover: false,
+        };
+        tree.root.tick(0, &mut behavior.memory, &mut actor);
+        let Actor {
+            speed,
+            facing,
+            fire,
+            in_cover,
+            ..
+        } = actor;
+
+        if !in_cover && let Some(cover) = cover.as_deref_mut() {
+            cover.release(entity);
+        }
+        let speed = speed.unwrap_or_else(|| {
+            agent.stop();
+            0.0
+        });
+        // Squadmates crowding a turret must not push it off its spot.
+        let separation = if enemy.speed > 0.0 {
+            squad.separation(entity, position, &squad_settings)
+        } else {
+            Vec3::ZERO
+        };
+        let velocity = agent.steer(position) * speed + separation;
+        let facing = if facing == Vec3::ZERO {
+            velocity.normalize_or_zero()
+        } else {
+            facing
+        };
+        drive_enemy(
+            time.delta_secs(),
+            &mut transform,
+            &mut controller,
+            &mut knockback,
+            velocity,
+            facing,
+        );
+
+        if fire {
+            let shot = EnemyShot {
+                shooter: entity,
+                team,
+                position,
+                player_position: player_transform.translation,
+                player_velocity: **player_velocity,
+                gravity,
+            };
+            fire_at_player(
+                &mut commands,
+                &mut meshes,
+                &mut materials,
+                &rules,
+                &mut enemy,
+                shot,
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Seconds every tick of the tests advances by.
+    const DELTA: f32 = 0.6;
+
+    /// Everything an `Actor` borrows, for ticking trees without a world.
+    struct Stub {
+        enemy: Enemy,
+        agent: NavAgent,
+        awareness: Awareness,
+        health: Health,
+        squad: Squad,
+        squad_settings: SquadSettings,
+        cover_settings: CoverSettings,
+    }
+
+    impl Default for Stub {
+        fn default() -> Self {
+            Self {
+                enemy: Enemy::default(),
+                agent: NavAgent::default(),
+                awareness: Awareness::default(),
+                health: Health::new(10),
+                squad: Squad::default(),
+                squad_settings: SquadSettings::default(),
+                cover_settings: CoverSettings::default(),
+            }
+        }
+    }
+
+    impl Stub {
+        fn tick(&mut self, root: &BehaviorNode, memory: &mut [NodeMemory]) -> Status {
+            let mut actor = Actor {
+                entity: Entity::PLACEHOLDER,
+                position: Vec3::ZERO,
+                delta: Duration::from_secs_f32(DELTA),
+                now: 0.0,
+                enemy: &mut self.enemy,
+                agent: &mut self.agent,
+                awareness: &self.awareness,
+                health: &self.health,
+                grid: None,
+                squad: &mut self.squad,
+                squad_settings: &self.squad_settings,
+                cover: None,
+                cover_settings: &self.cover_settings,
+                rapier: None,
+                speed: None,
+                facing: Vec3::ZERO,
+                fire: false,
+                in_cover: false,
+            };
+            root.tick(0, memory, &mut actor)
+        }
+    }
+
+    fn memory_for(root: &BehaviorNode) -> Vec<NodeMemory> {
+        vec![NodeMemory::default(); root.size()]
+    }
+
+    fn assert_elapsed(memory: &NodeMemory, seconds: f32) {
+        assert!(
+            (memory.elapsed - seconds).abs() < 1.0e-5,
+            "elapsed {} instead of {seconds}",
+            memory.elapsed
+        );
+    }
+
+    #[test]
+    fn sequence_resumes_at_the_running_child() {
+        let root = BehaviorNode::Sequence(vec![BehaviorNode::Wait(1.0), BehaviorNode::Wait(1.0)]);
+        let mut memory = memory_for(&root);
+        let mut stub = Stub::default();
+
+        assert_eq!(stub.tick(&root, &mut memory), Status::Running);
+        assert_eq!(memory[0].child, 0);
+        assert_eq!(stub.tick(&root, &mut memory), Status::Running);
+        assert_eq!(memory[0].child, 1);
+        assert_elapsed(&memory[1], 0.0);
+        assert_elapsed(&memory[2], DELTA);
+        assert_eq!(stub.tick(&root, &mut memory), Status::Success);
+        assert_eq!(memory[0].child, 0);
+        assert_elapsed(&memory[2], 0.0);
+    }
+
+    #[test]
+    fn selector_preemption_resets_the_lower_branch() {
+        // Memory: 0 selector, 1 while, 2 long wait, 3 short wait.
+        let root = BehaviorNode::Selector(vec![
+            BehaviorNode::While {
+                condition: Condition::SeesPlayer,
+                child: Box::new(BehaviorNode::Wait(10.0)),
+            },
+            BehaviorNode::Wait(5.0),
+        ]);
+        let mut memory = memory_for(&root);
+        let mut stub = Stub::default();
+
+        assert_eq!(stub.tick(&root, &mut memory), Status::Running);
+        assert_eq!(memory[0].child, 1);
+        assert_elapsed(&memory[3], DELTA);
+
+        stub.awareness.sees_player = true;
+        assert_eq!(stub.tick(&root, &mut memory), Status::Running);
+        assert_eq!(memory[0].child, 0);
+        assert_elapsed(&memory[2], DELTA);
+        assert_elapsed(&memory[3], 0.0);
+
+        // Falling back starts the lower branch over instead of picking up where it was.
+        stub.awareness.sees_player = false;
+        assert_eq!(stub.tick(&root, &mut memory), Status::Running);
+        assert_eq!(memory[0].child, 1);
+        assert_elapsed(&memory[2], 0.0);
+        assert_elapsed(&memory[3], DELTA);
+    }
+
+    #[test]
+    fn parallel_requiring_all_waits_for_every_child() {
+        let root = BehaviorNode::Parallel {
+            policy: ParallelPolicy::RequireAll,
+            children: vec![BehaviorNode::Wait(0.5), BehaviorNode::Wait(1.0)],
+        };
+        let mut memory = memory_for(&root);
+        let mut stub = Stub::default();
+
+        assert_eq!(stub.tick(&root, &mut memory), Status::Running);
+        assert_eq!(memory[0].succeeded, 0b01);
+        assert_eq!(stub.tick(&root, &mut memory), Status::Success);
+        assert_eq!(memory[0].succeeded, 0);
+    }
+
+    #[test]
+    fn parallel_requiring_all_fails_with_any_child() {
+        let root = BehaviorNode::Parallel {
+            policy: ParallelPolicy::RequireAll,
+            children: vec![
+                BehaviorNode::Wait(1.0),
+                BehaviorNode::Check(Condition::SeesPlayer),
+            ],
+        };
+        let mut memory = memory_for(&root);
+
+        assert_eq!(Stub::default().tick(&root, &mut memory), Status::Failure);
+        assert_elapsed(&memory[1], 0.0);
+    }
+
+    #[test]
+    fn parallel_requiring_one_succeeds_with_any_child() {
+        let root = BehaviorNode::Parallel {
+            policy: ParallelPolicy::RequireOne,
+            children: vec![
+                BehaviorNode::Check(Condition::SeesPlayer),
+                BehaviorNode::Wait(1.0),
+            ],
+        };
+        let mut memory = memory_for(&root);
+        let mut stub = Stub::default();
+
+        assert_eq!(stub.tick(&root, &mut memory), Status::Running);
+        assert_eq!(memory[0].failed, 0b01);
+        assert_eq!(stub.tick(&root, &mut memory), Status::Success);
+    }
+
+    #[test]
+    fn parallel_requiring_one_fails_once_every_child_did() {
+        let root = BehaviorNode::Parallel {
+            policy: ParallelPolicy::RequireOne,
+            children: vec![
+                BehaviorNode::Check(Condition::SeesPlayer),
+                BehaviorNode::Check(Condition::KnowsPlayer),
+            ],
+        };
+        let mut memory = memory_for(&root);
+
+        assert_eq!(Stub::default().tick(&root, &mut memory), Status::Failure);
+    }
+
+    #[test]
+    fn rejects_parallel_nodes_with_too_many_children() {
+        let parallel = |count| BehaviorNode::Parallel {
+            policy: ParallelPolicy::RequireAll,
+            children: vec![BehaviorNode::Wait(1.0); count],
+        };
+
+        assert!(parallel(MAX_PARALLEL_CHILDREN).validate().is_ok());
+        let nested = BehaviorNode::Invert(Box::new(parallel(MAX_PARALLEL_CHILDREN + 1)));
+        assert!(nested.validate().is_err());
+    }
+}
@@ -0,0 +1,171 @@
+use bevy::platform::collections::HashMap;
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+
+use crate::navigation::{NavGrid, level_filter};
+
+/// Where cover points are put around obstacles and how enemies pick between them.
+#[derive(Debug, Clone, Resource)]
+pub struct CoverSettings {
+    /// Obstacles with a footprint wider than this, like the floor, don't count as cover, in meters.
+    pub max_obstacle_size: f32,
+    /// Obstacles whose bottom is higher off the floor than this can't be hidden behind, in meters.
+    pub max_obstacle_base: f32,
+    /// Distance of the cover points from the faces of their obstacle, in meters.
+    pub offset: f32,
+    /// Distance between neighbouring cover points along a face, in meters.
+    pub spacing: f32,
+    /// Farthest an enemy runs to get into cover, in meters.
+    pub search_radius: f32,
+    /// Height above the floor of the point that has to be out of the player's sight.
+    pub hide_height: f32,
+    /// Height of the player's eyes above their last known position.
+    pub threat_eye_height: f32,
+}
+
+impl Default for CoverSettings {
+    fn default() -> Self {
+        Self {
+            max_obstacle_size: 20.0,
+            max_obstacle_base: 0.5,
+            offset: 0.8,
+            spacing: 1.0,
+            search_radius: 12.0,
+            hide_height: 0.25,
+            threat_eye_height: 0.7,
+        }
+    }
+}
+
+/// Spots on the floor next to obstacles, built from the fixed colliders of the world.
+#[derive(Debug, Clone, Resource)]
+pub struct CoverPoints {
+    pub points: Vec<Vec3>,
+    /// Point every enemy in cover has claimed, so no two of them hide in the same spot.
+    claims: HashMap<Entity, usize>,
+}
+
+impl CoverPoints {
+    /// The spot `entity` should hide in from a player last seen at `threat`.
+    ///
+    /// Keeps the spot it already has while that is still out of sight, and otherwise claims
+    /// the closest free one that is. Returns `None` if there is no hidden spot in reach.
+    pub fn claim(
+        &mut self,
+        entity: Entity,
+        position: Vec3,
+        threat: Vec3,
+        context: &RapierContext,
+        settings: &CoverSettings,
+    ) -> Option<Vec3> {
+        let eyes = threat + Vec3::Y * settings.threat_eye_height;
+        // Only the level blocks sight.
+        let hidden = |point: Vec3| {
+            let target = point.with_y(settings.hide_height);
+            let offset = target - eyes;
+            let distance = offset.length();
+            distance > 0.0
+                && context
+                    .cast_ray(eyes, offset / distance, distance, true, level_filter())
+                    .is_some()
+        };
+
+        if let Some(&index) = self.claims.get(&entity)
+            && hidden(self.points[index])
+        {
+            return Some(self.points[index]);
+        }
+
+        let mut candidates: Vec<(usize, f32)> = self
+            .points
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| {
+                !self
+                    .claims
+                    .iter()
+                    .any(|(other, claimed)| *other != entity && claimed == index)
+            })
+            .map(|(index, point)| (index, point.xz().distance(position.xz())))
+            .filter(|(_, distance)| *distance <= settings.search_radius)
+            .collect();
+        // Try the closest spots first, so only as many rays are cast as needed.
+        candidates.sort_by(|(_, a), (_, b)| a.total_cmp(b));
+        let best = candidates
+            .into_iter()
+            .find(|(index, _)| hidden(self.points[*index]))
+            .map(|(index, _)| index);
+
+        match best {
+            Some(index) => {
+                self.claims.insert(entity, index);
+                Some(self.points[index])
+            }
+            None => {
+                self.release(entity);
+                None
+            }
+        }
+    }
+
+    /// Gives up the spot `entity` claimed, if any.
+    pub fn release(&mut self, entity: Entity) {
+        self.claims.remove(&entity);
+    }
+}
+
+/// Builds the `CoverPoints` around the fixed box colliders standing on the floor,
+/// once the `NavGrid` tells which spots can be reached.
+pub fn build_cover_points(
+    mut commands: Commands,
+    settings: Res<CoverSettings>,
+    grid: Res<NavGrid>,
+    obstacles: Query<(&RigidBody, &Collider, &GlobalTransform)>,
+) {
+    let mut points = Vec::new();
+    for (rigid_body, collider, transform) in &obstacles {
+        if *rigid_body != RigidBody::Fixed {
+            continue;
+        }
+        let Some(cuboid) = collider.as_cuboid() else {
+            continue;
+        };
+        let (scale, rotation, center) = transform.to_scale_rotation_translation();
+        let half_size = cuboid.half_extents() * scale;
+        if half_size.x.max(half_size.z) * 2.0 > settings.max_obstacle_size
+            || center.y - half_size.y > settings.max_obstacle_base
+        {
+            continue;
+        }
+
+        // Line every side of the box with points, facing away from it.
+        for (normal, along, depth, width) in [
+            (Vec3::X, Vec3::Z, half_size.x, half_size.z),
+            (Vec3::NEG_X, Vec3::Z, half_size.x, half_size.z),
+            (Vec3::Z, Vec3::X, half_size.z, half_size.x),
+            (Vec3::NEG_Z, Vec3::X, half_size.z, half_size.x),
+        ] {
+            let count = ((width * 2.0) / settings.spacing).floor().max(0.0) as i32;
+            for step in 0..=count {
+                let across = -width
+                    + (width * 2.0 - count as f32 * settings.spacing) / 2.0
+                    + step as f32 * settings.spacing;
+                let local = normal * (depth + settings.offset) + along * across;
+                let point = (center + rotation * local).with_y(0.0);
+                // Close to the box the cell may count as blocked, so step out to the next free one.
+                if let Some(spot) = grid
+                    .nearest_walkable(point)
+                    .filter(|spot| spot.xz().distance(point.xz()) <= settings.spacing)
+                {
+                    points.push(spot);
+                }
+            }
+        }
+    }
+
+    info!("Found {} cover points", points.len());
+    commands.insert_resource(CoverPoints {
+        points,
+        claims: HashMap::default(),
+    });
+}
@@ -1,6 +1,13 @@
 use bevy::prelude::*;
 
-pub fn spawn_crosshair(mut commands: Commands, asset_server: Res<AssetServer>) {
+use crate::entity::Player;
+use crate::weapon::Weapon;
+
+/// Tag for the text showing the rounds of the player's weapon.
+#[derive(Component)]
+pub struct AmmoCounter;
+
+pub fn spawn_crosshair(mut commands: Commands) {
     commands
         .spawn(Node {
             position_type: PositionType::Absolute,
@@ -8,5 +15,39 @@ pub fn spawn_crosshair(mut commands: Commands, asset_server: Res<AssetServer>) {
             bottom: Val::Percent(50.0),
             ..default()
         })
-        .with_child(Text::new(concat!("+")));
+        .with_child(Text::new("+"));
+
+    // Just below and to the right of the crosshair, where the eyes already are.
+    commands
+        .spawn(Node {
+            position_type: PositionType::Absolute,
+            left: Val::Percent(50.0),
+            top: Val::Percent(50.0),
+            margin: UiRect {
+                left: Val::Px(32.0),
+                top: Val::Px(16.0),
+                ..default()
+            },
+            ..default()
+        })
+        .with_child((AmmoCounter, Text::default()));
+}
+
+pub fn update_ammo_counter(
+    weapon_query: Query<&Weapon, (With<Player>, Changed<Weapon>)>,
+    mut counter_query: Query<&mut Text, With<AmmoCounter>>,
+) {
+    let Ok(weapon) = weapon_query.single() else {
+        return;
+    };
+
+    for mut text in &mut counter_query {
+        text.0 = if weapon.is_out_of_ammo() {
+            "OUT OF AMMO".to_string()
+        } else if weapon.is_reloading() {
+            format!("RELOADING  {} / {}", weapon.magazine, weapon.reserve)
+        } else {
+            format!("{} / {}", weapon.magazine, weapon.reserve)
+        };
+    }
 }
@@ -0,0 +1,236 @@
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+
+use crate::entity::{Health, Owner, Projectile, Team};
+
+/// Collision group every projectile is a member of.
+pub const PROJECTILE_GROUP: Group = Group::GROUP_4;
+
+/// Who projectiles are allowed to hurt. By default they only hurt the other team.
+#[derive(Debug, Default, Clone, Resource)]
+pub struct DamageRules {
+    /// Projectiles hurt combatants on the same team as their shooter.
+    pub friendly_fire: bool,
+    /// Projectiles hurt the combatant that fired them.
+    pub self_damage: bool,
+}
+
+impl DamageRules {
+    /// Whether a projectile fired by `owner` of `team` may hurt `target` of `target_team`.
+    /// Projectiles or targets without an owner or team are not restricted.
+    pub fn allows(
+        &self,
+        owner: Option<Entity>,
+        team: Option<Team>,
+        target: Entity,
+        target_team: Option<Team>,
+    ) -> bool {
+        if owner == Some(target) {
+            return self.self_damage;
+        }
+        if team.is_some() && team == target_team {
+            return self.friendly_fire;
+        }
+        true
+    }
+
+    /// Collision groups for a projectile fired by a combatant of `team`.
+    ///
+    /// Without friendly fire the projectile passes through the whole team, shooter included.
+    /// Groups only work per team, so with friendly fire the shooter can still be bumped by its
+    /// own projectiles, which `allows` then keeps from hurting unless self damage is on.
+    pub fn projectile_groups(&self, team: Team) -> CollisionGroups {
+        let filters = if self.friendly_fire {
+            Group::ALL
+        } else {
+            Group::ALL - team.group()
+        };
+        CollisionGroups::new(PROJECTILE_GROUP, filters)
+    }
+}
+
+/// Asks for `amount` hit points to be taken from `target`.
+#[derive(Debug, Clone, Event)]
+pub struct DamageEvent {
+    pub target: Entity,
+    pub amount: i32,
+    /// Whoever dealt the damage, if known.
+    pub source: Option<Entity>,
+}
+
+/// Sent by `apply_health_events` for the hit points a `DamageEvent` actually took,
+/// leaving out overkill and damage to the already dead.
+#[derive(Debug, Clone, Event)]
+pub struct DamageDealtEvent {
+    pub target: Entity,
+    pub amount: i32,
+    /// Whoever dealt the damage, if known.
+    pub source: Option<Entity>,
+}
+
+/// Asks for `amount` hit points to be given back to `target`, up to its maximum.
+#[derive(Debug, Clone, Event)]
+pub struct HealEvent {
+    pub target: Entity,
+    pub amount: i32,
+}
+
+/// Sent when a projectile runs into something it is allowed to hurt.
+#[derive(Debug, Clone, Event)]
+pub struct HitEvent {
+    pub target: Entity,
+    /// Whoever fired the projectile, if known.
+    pub source: Option<Entity>,
+    /// Where the projectile was when it hit.
+    pub position: Vec3,
+    /// Velocity the hit adds to the target.
+    pub impulse: Vec3,
+}
+
+/// Velocity pushed onto a character by hits, fading away over time.
+///
+/// Character controllers have no velocity of their own for the solver to change,
+/// so whatever moves the character adds this on top.
+#[derive(Debug, Default, Clone, Component, Deref, DerefMut)]
+pub struct Knockback(pub Vec3);
+
+/// Brings a dead `target` back at full health.
+#[derive(Debug, Clone, Event)]
+pub struct ReviveEvent {
+    pub target: Entity,
+}
+
+/// Sent exactly once when an entity's health drops to zero.
+#[derive(Debug, Clone, Event)]
+pub struct DeathEvent {
+    pub entity: Entity,
+    /// Source of the damage that killed it, if known.
+    pub killer: Option<Entity>,
+}
+
+/// Turns projectiles running into anything with `Health` into `HitEvent`s and `DamageEvent`s,
+/// following the `DamageRules`.
+pub fn handle_collisions(
+    mut commands: Commands,
+    rules: Res<DamageRules>,
+    mut collision_events: EventReader<CollisionEvent>,
+    mut hit_events: EventWriter<HitEvent>,
+    mut damage_events: EventWriter<DamageEvent>,
+    projectile_query: Query<(&Projectile, Option<&Owner>, Option<&Team>, &GlobalTransform)>,
+    target_query: Query<(Option<&Team>, &GlobalTransform), With<Health>>,
+) {
+    for collision_event in collision_events.read() {
+        if let CollisionEvent::Started(entity_a, entity_b, _) = collision_event {
+            // 1. Try to see if A is projectile and B has health
+            // 2. Try to see if B is projectile and A has health
+            let hit = if let Ok(p) = projectile_query.get(*entity_a) {
+                Some((*entity_a, p, *entity_b))
+            } else if let Ok(p) = projectile_query.get(*entity_b) {
+                Some((*entity_b, p, *entity_a))
+            } else {
+                None
+            };
+
+            // If we found a valid Health + Projectile pair, apply damage
+            if let Some((projectile_entity, (projectile, owner, team, position), target)) = hit
+                && let Ok((target_team, target_transform)) = target_query.get(target)
+                && rules.allows(
+                    owner.map(|owner| owner.0),
+                    team.copied(),
+                    target,
+                    target_team.copied(),
+                )
+            {
+                // The velocity of the projectile already bounced off the target by now,
+                // so push the target away from where it was hit instead.
+                let direction =
+                    (target_transform.translation() - position.translation()).normalize_or_zero();
+                hit_events.write(HitEvent {
+                    target,
+                    source: owner.map(|owner| owner.0),
+                    position: position.translation(),
+                    impulse: direction * projectile.knockback,
+                });
+                damage_events.write(DamageEvent {
+                    target,
+                    amount: projectile.damage,
+                    source: owner.map(|owner| owner.0),
+                });
+
+                // A projectile only hurts once, afterwards it is just a bouncing ball.
+                commands.entity(projectile_entity).remove::<Projectile>();
+            }
+        }
+    }
+}
+
+/// Applies damage and healing to `Health`, clamped between zero and the maximum,
+/// sends a `DamageDealtEvent` for the health taken and a `DeathEvent` when it runs out.
+///
+/// The dead stay dead until revived: they take no more damage and can't be healed,
+/// so death is only reported once.
+pub fn apply_health_events(
+    mut damage_events: EventReader<DamageEvent>,
+    mut heal_events: EventReader<HealEvent>,
+    mut revive_events: EventReader<ReviveEvent>,
+    mut dealt_events: EventWriter<DamageDealtEvent>,
+    mut death_events: EventWriter<DeathEvent>,
+    mut health_query: Query<&mut Health>,
+) {
+    for damage in damage_events.read() {
+        let Ok(mut health) = health_query.get_mut(damage.target) else {
+            continue;
+        };
+        if health.is_dead() {
+            continue;
+        }
+
+        let before = health.current;
+        health.current = (health.current - damage.amount).clamp(0, health.max);
+        if health.current < before {
+            dealt_events.write(DamageDealtEvent {
+                target: damage.target,
+                amount: before - health.current,
+                source: damage.source,
+            });
+        }
+        info!(
+            "{} hit! Health remaining: {}",
+            damage.target, health.current
+        );
+
+        if health.is_dead() {
+            death_events.write(DeathEvent {
+                entity: damage.target,
+                killer: damage.source,
+            });
+        }
+    }
+
+    for heal in heal_events.read() {
+        if let Ok(mut health) = health_query.get_mut(heal.target)
+            && !health.is_dead()
+        {
+            health.current = (health.current + heal.amount).clamp(0, health.max);
+        }
+    }
+
+    for revive in revive_events.read() {
+        if let Ok(mut health) = health_query.get_mut(revive.target) {
+            health.current = health.max;
+        }
+    }
+}
+
+/// Adds the impulse of hits to the `Knockback` of their targets.
+pub fn apply_knockback(
+    mut hit_events: EventReader<HitEvent>,
+    mut knockback_query: Query<&mut Knockback>,
+) {
+    for hit in hit_events.read() {
+        if let Ok(mut knockback) = knockback_query.get_mut(hit.target) {
+            // Knocking characters into the air or the floor would only fight their gravity.
+            **knockback += hit.impulse.with_y(0.0);
+        }
+    }
+}
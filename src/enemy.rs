@@ -2,124 +2,621 @@ use bevy::pbr::StandardMaterial;
 use bevy::prelude::*;
 use bevy_rapier3d::prelude::*;
 
+use crate::aiming::{scatter, solve_ballistic_aim};
+use crate::archetype::{Archetype, EnemyArchetype};
 use crate::ball::{Ball, BallBundle};
-use crate::entity::{Health, Player, Projectile};
+use crate::behavior::Behavior;
+use crate::cover::{CoverPoints, CoverSettings};
+use crate::damage::{DamageEvent, DamageRules, DeathEvent, Knockback, PROJECTILE_GROUP};
+use crate::entity::{Health, Owner, Player, Projectile, Team};
+use crate::movement::PlayerVelocity;
+use crate::navigation::{NavAgent, NavGrid};
+use crate::perception::{Awareness, Perception};
+use crate::squad::{Squad, SquadSettings};
 
-#[derive(Component)]
+/// Seconds an enemy takes to shrink away after dying.
+const DEATH_DURATION: f32 = 0.5;
+
+/// Seconds an enemy stands around before patrolling again.
+const IDLE_TIME: f32 = 2.0;
+
+/// Seconds an enemy stays in cover after taking damage.
+const COVER_TIME: f32 = 2.0;
+
+/// Seconds an enemy patrols before taking a break.
+const PATROL_TIME: f32 = 6.0;
+
+/// Patrolling and searching enemies walk at this fraction of their speed.
+const WANDER_SPEED_FACTOR: f32 = 0.5;
+
+/// Farthest a patrolling or searching enemy walks to its next spot, in meters.
+const WANDER_RADIUS: f32 = 6.0;
+
+/// How far ahead of the player a fleeing enemy plans its escape, in meters.
+const FLEE_DISTANCE: f32 = 8.0;
+
+/// Horizontal distance at which a chasing enemy counts as having reached the last known position.
+const ARRIVAL_DISTANCE: f32 = 1.0;
+
+/// Linear damping of enemy projectiles, which the aim has to make up for.
+const PROJECTILE_DAMPING: f32 = 0.5;
+
+/// Seconds an enemy projectile lives, and so the longest flight the aim considers.
+const PROJECTILE_LIFETIME: f32 = 5.0;
+
+/// Half-angle of the cone the shots of an enemy without any accuracy scatter in.
+const MAX_AIM_ERROR: f32 = 0.15;
+
+/// How quickly knockback fades, per second.
+const KNOCKBACK_DECAY: f32 = 6.0;
+
+/// Speed enemies fall with. The character controller keeps them from sinking into the floor.
+const FALL_SPEED: f32 = 9.81;
+
+/// Stats and timers of an enemy. The stats come from its `EnemyArchetype`.
+#[derive(Default, Component)]
 pub struct Enemy {
+    /// Picks a new spot to walk to while patrolling or searching.
     pub move_timer: Timer,
+    /// Runs once per shot. A finished timer holds its shot until the squad lets it fire.
     pub shoot_timer: Timer,
-    pub current_direction: Vec3,
+    /// Runs the timed states, `Idle` and `Patrol`.
+    pub state_timer: Timer,
+    /// Runs while the magazine is empty, refilling it when done.
+    pub reload_timer: Timer,
+    /// Runs after taking damage, for as long as the enemy stays in cover.
+    pub cover_timer: Timer,
+    pub magazine_size: u32,
+    /// Shots left before reloading.
+    pub magazine: u32,
+    /// Meters per second.
+    pub speed: f32,
+    /// Distance to the player from which the enemy stops and shoots.
+    pub attack_range: f32,
+    /// Fraction of its health below which the enemy flees from the player.
+    pub flee_below: f32,
+    /// Skill at hitting the player, between 0 and 1. A perfect shot leads the player fully
+    /// and doesn't scatter, while lower values lead less and scatter up to `MAX_AIM_ERROR`.
+    pub accuracy: f32,
+    /// Speed projectiles leave the muzzle with, in meters per second.
+    pub projectile_speed: f32,
+    pub projectile_damage: i32,
+    pub projectile_knockback: f32,
+    pub projectile_color: Color,
+}
+
+impl Enemy {
+    pub fn reloading(&self) -> bool {
+        self.magazine == 0
+    }
+
+    /// The wait since the last shot is over and there is a round left.
+    pub fn ready_to_fire(&self) -> bool {
+        self.shoot_timer.finished() && !self.reloading()
+    }
+
+    /// Took damage a moment ago and wants to get out of sight.
+    pub fn shaken(&self) -> bool {
+        !self.cover_timer.finished()
+    }
+}
+
+/// How much tougher than its archetype an enemy is, set by the wave it spawned in.
+#[derive(Debug, Clone, Component)]
+pub struct Difficulty {
+    /// Multiplies the health of the archetype.
+    pub health: f32,
+    /// Multiplies the speed of the archetype.
+    pub speed: f32,
+    /// Added to the accuracy of the archetype.
+    pub accuracy: f32,
+}
+
+impl Default for Difficulty {
+    fn default() -> Self {
+        Self {
+            health: 1.0,
+            speed: 1.0,
+            accuracy: 0.0,
+        }
+    }
+}
+
+/// What an enemy is doing, decided by `update_enemy_state` from what it perceives.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Component)]
+pub enum EnemyState {
+    /// Standing around.
+    #[default]
+    Idle,
+    /// Wandering in random directions.
+    Patrol,
+    /// Running towards where the player is, or was last seen or heard.
+    Chase,
+    /// Shooting at the player in sight, standing still unless its squad role moves it.
+    Attack,
+    /// Running away from the player.
+    Flee,
+    /// Hiding from the player behind an obstacle while reloading or after taking damage.
+    TakeCover,
+    /// Looking around the last known position of the player after losing track of them.
+    Search,
+}
+
+/// An enemy that ran out of health and is playing its death sequence before despawning.
+#[derive(Component)]
+pub struct Dying {
+    pub timer: Timer,
 }
 
 #[derive(Bundle)]
 pub struct EnemyBundle {
     pub enemy: Enemy,
+    pub archetype: Archetype,
+    pub difficulty: Difficulty,
+    pub state: EnemyState,
+    pub perception: Perception,
+    pub awareness: Awareness,
+    pub nav_agent: NavAgent,
+    pub knockback: Knockback,
+    pub health: Health,
+    pub team: Team,
+    pub rigid_body: RigidBody,
+    pub controller: KinematicCharacterController,
+    pub collider: Collider,
+    pub collision_groups: CollisionGroups,
+    pub active_events: ActiveEvents,
     pub mesh: Mesh3d,
     pub material: MeshMaterial3d<StandardMaterial>,
     pub transform: Transform,
 }
 
+/// Moves every enemy between its states, based on what it knows about the player and its health.
+pub fn update_enemy_state(
+    time: Res<Time>,
+    mut damage_events: EventReader<DamageEvent>,
+    mut enemy_query: Query<
+        (&Transform, &mut Enemy, &mut EnemyState, &Awareness, &Health),
+        Without<Dying>,
+    >,
+) {
+    for damage in damage_events.read() {
+        if damage.amount > 0
+            && let Ok((_, mut enemy, ..)) = enemy_query.get_mut(damage.target)
+        {
+            enemy.cover_timer = Timer::from_seconds(COVER_TIME, TimerMode::Once);
+        }
+    }
+
+    for (transform, mut enemy, mut state, awareness, health) in &mut enemy_query {
+        enemy.state_timer.tick(time.delta());
+        enemy.cover_timer.tick(time.delta());
+        if enemy.reloading() {
+            enemy.reload_timer.tick(time.delta());
+            if enemy.reload_timer.finished() {
+                enemy.reload_timer.reset();
+                enemy.magazine = enemy.magazine_size;
+            }
+        }
+        let distance = awareness
+            .last_known_position
+            .map(|position| (position - transform.translation).xz().length());
+
+        let next = match (awareness.sees_player, distance) {
+            (true, _) if health.fraction() < enemy.flee_below => EnemyState::Flee,
+            (_, Some(_)) if enemy.reloading() || enemy.shaken() => EnemyState::TakeCover,
+            (true, Some(distance)) if distance <= enemy.attack_range => EnemyState::Attack,
+            (true, _) => EnemyState::Chase,
+            // Lost sight of the player, but still remembers where they were.
+            (false, Some(_)) if *state == EnemyState::Flee => EnemyState::Flee,
+            (false, Some(_)) if *state == EnemyState::Search => EnemyState::Search,
+            (false, Some(distance)) if distance <= ARRIVAL_DISTANCE => EnemyState::Search,
+            (false, Some(_)) => EnemyState::Chase,
+            // Forgot about the player.
+            (false, None) => match *state {
+                EnemyState::Idle if enemy.state_timer.finished() => EnemyState::Patrol,
+                EnemyState::Patrol if enemy.state_timer.finished() => EnemyState::Idle,
+                EnemyState::Patrol => EnemyState::Patrol,
+                _ => EnemyState::Idle,
+            },
+        };
+
+        if next != *state {
+            debug!("Enemy {:?} -> {:?}", *state, next);
+            let duration = match next {
+                EnemyState::Patrol => PATROL_TIME,
+                _ => IDLE_TIME,
+            };
+            enemy.state_timer = Timer::from_seconds(duration, TimerMode::Once);
+            *state = next;
+        }
+    }
+}
+
+/// Moves and shoots according to the state every enemy is in.
+///
+/// Enemies with a `Behavior` are left to `run_behaviors`.
 pub fn enemy_ai(
     mut commands: Commands,
     time: Res<Time>,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<StandardMaterial>>,
+    rules: Res<DamageRules>,
+    grid: Option<Res<NavGrid>>,
+    squad_settings: Res<SquadSettings>,
+    mut squad: ResMut<Squad>,
+    rapier_context: ReadRapierContext,
+    cover_settings: Res<CoverSettings>,
+    mut cover: Option<ResMut<CoverPoints>>,
     // Query the enemy
-    mut enemy_query: Query<(&mut Transform, &mut Enemy), Without<Player>>,
+    mut enemy_query: Query<
+        (
+            Entity,
+            &mut Transform,
+            &mut Enemy,
+            &mut NavAgent,
+            &mut Knockback,
+            &mut KinematicCharacterController,
+            &EnemyState,
+            &Awareness,
+            &Team,
+        ),
+        (Without<Player>, Without<Dying>, Without<Behavior>),
+    >,
     // Query the player position
-    player_query: Query<&Transform, With<Player>>,
+    player_query: Query<(&Transform, &PlayerVelocity), With<Player>>,
+    physics: Query<&RapierConfiguration>,
 ) {
-    let Ok(player_transform) = player_query.single() else {
+    let Ok((player_transform, player_velocity)) = player_query.single() else {
         return;
     };
     let player_pos = player_transform.translation;
+    let gravity = physics
+        .single()
+        .map_or(Vec3::NEG_Y * 9.81, |config| config.gravity);
+    let grid = grid.as_deref();
+    let context = rapier_context.single().ok();
+
+    for (
+        shooter,
+        mut enemy_transform,
+        mut enemy,
+        mut agent,
+        mut knockback,
+        mut controller,
+        state,
+        awareness,
+        &team,
+    ) in &mut enemy_query
+    {
+        // Flat direction towards where the player is believed to be.
+        let towards_player = awareness
+            .last_known_position
+            .map(|position| {
+                (position - enemy_transform.translation)
+                    .with_y(0.0)
+                    .normalize_or_zero()
+            })
+            .unwrap_or(Vec3::ZERO);
 
-    for (mut enemy_transform, mut enemy) in &mut enemy_query {
-        // --- 1. RANDOM MOVEMENT ---
-        enemy.move_timer.tick(time.delta());
-        if enemy.move_timer.just_finished() {
-            // Pick a random direction on the XZ plane
-            let angle = rand::random::<f32>() * std::f32::consts::TAU;
-            enemy.current_direction = Vec3::new(angle.cos(), 0.0, angle.sin());
+        // --- 1. MOVEMENT ---
+        let position = enemy_transform.translation;
+        let reachable = |target: Vec3| {
+            grid.and_then(|grid| grid.nearest_walkable(target))
+                .unwrap_or(target)
+        };
+        // Flankers and retreating enemies head for where their role wants them, shooting on the way.
+        let post = match state {
+            EnemyState::Attack | EnemyState::Chase => squad
+                .post(shooter, position, enemy.attack_range, &squad_settings)
+                .filter(|post| post.xz().distance(position.xz()) > ARRIVAL_DISTANCE),
+            _ => None,
+        };
+        if *state != EnemyState::TakeCover
+            && let Some(cover) = cover.as_deref_mut()
+        {
+            cover.release(shooter);
         }
+        let speed = match state {
+            _ if let Some(post) = post => {
+                agent.go_to(reachable(post), position, grid);
+                enemy.speed
+            }
+            EnemyState::Idle | EnemyState::Attack => {
+                agent.stop();
+                0.0
+            }
+            EnemyState::Patrol | EnemyState::Search => {
+                enemy.move_timer.tick(time.delta());
+                if enemy.move_timer.just_finished() || agent.arrived() {
+                    // Patrol around where it is, and search around where the player was.
+                    let center = match state {
+                        EnemyState::Search => awareness.last_known_position.unwrap_or(position),
+                        _ => position,
+                    };
+                    // Pick a random spot on the XZ plane
+                    let angle = rand::random::<f32>() * std::f32::consts::TAU;
+                    let distance = rand::random::<f32>() * WANDER_RADIUS;
+                    let spot = center + Vec3::new(angle.cos(), 0.0, angle.sin()) * distance;
+                    agent.go_to(reachable(spot), position, grid);
+                }
+                enemy.speed * WANDER_SPEED_FACTOR
+            }
+            EnemyState::Chase => {
+                if let Some(target) = awareness.last_known_position {
+                    agent.go_to(target, position, grid);
+                }
+                enemy.speed
+            }
+            EnemyState::TakeCover => {
+                let spot = match (
+                    cover.as_deref_mut(),
+                    &context,
+                    awareness.last_known_position,
+                ) {
+                    (Some(cover), Some(context), Some(threat)) => {
+                        cover.claim(shooter, position, threat, context, &cover_settings)
+                    }
+                    _ => None,
+                };
+                // Without any cover around, back away from the player instead.
+                let spot = spot.unwrap_or(position - towards_player * FLEE_DISTANCE);
+                agent.go_to(reachable(spot), position, grid);
+                enemy.speed
+            }
+            EnemyState::Flee => {
+                let escape = position - towards_player * FLEE_DISTANCE;
+                agent.go_to(reachable(escape), position, grid);
+                enemy.speed
+            }
+        };
+        // Keep clear of the other enemies instead of bunching up on the same path.
+        // Turrets stay where they spawned, however crowded it gets.
+        let separation = if enemy.speed > 0.0 {
+            squad.separation(shooter, position, &squad_settings)
+        } else {
+            Vec3::ZERO
+        };
+        let velocity = agent.steer(position) * speed + separation;
 
-        // Move the enemy
-        enemy_transform.translation += enemy.current_direction * time.delta_secs() * 2.0;
+        // Face the player while shooting, and where it's going otherwise, since that's where it looks.
+        let facing = if *state == EnemyState::Attack {
+            towards_player
+        } else {
+            velocity.normalize_or_zero()
+        };
+        drive_enemy(
+            time.delta_secs(),
+            &mut enemy_transform,
+            &mut controller,
+            &mut knockback,
+            velocity,
+            facing,
+        );
 
         // --- 2. SHOOTING AT PLAYER ---
+        if *state != EnemyState::Attack {
+            continue;
+        }
         enemy.shoot_timer.tick(time.delta());
-        if enemy.shoot_timer.just_finished() {
-            let spawn_pos = enemy_transform.translation + Vec3::Y * 0.5; // Spawn slightly above
-
-            // Calculate direction vector: (Target - Start) normalized
-            let shoot_dir = (player_pos - spawn_pos).normalize();
-            let velocity = shoot_dir * 20.0; // Speed of the ball
-
-            // Use the BallBundle from the previous step
-            commands.spawn((
-                BallBundle {
-                    ball: Ball {
-                        lifetime: Timer::from_seconds(5.0, TimerMode::Once),
-                    },
-                    rigid_body: RigidBody::Dynamic,
-                    mesh: Mesh3d(meshes.add(Sphere::new(0.3))),
-                    material: MeshMaterial3d(materials.add(Color::from(Srgba::RED))),
-                    collider: Collider::ball(0.3),
-                    restitution: Restitution::coefficient(0.8),
-                    friction: Friction::coefficient(5.0),
-                    damping: Damping {
-                        linear_damping: 0.5,
-                        angular_damping: 0.5,
-                    },
-                    transform: Transform::from_translation(spawn_pos),
-                    velocity: Velocity::linear(velocity),
-                },
-                Projectile { damage: 1 },
-            ));
+        if enemy.ready_to_fire()
+            && squad.take_shot(time.elapsed_secs(), squad_settings.shot_spacing)
+        {
+            let shot = EnemyShot {
+                shooter,
+                team,
+                position: enemy_transform.translation,
+                player_position: player_pos,
+                player_velocity: **player_velocity,
+                gravity,
+            };
+            fire_at_player(
+                &mut commands,
+                &mut meshes,
+                &mut materials,
+                &rules,
+                &mut enemy,
+                shot,
+            );
         }
     }
 }
 
-pub fn spawn_enemy(
-    mut commands: Commands,
-    mut meshes: ResMut<Assets<Mesh>>,
-    mut materials: ResMut<Assets<StandardMaterial>>,
+/// Walks an enemy at `velocity` through its character controller, so it slides along the
+/// world instead of walking through it, and turns it to look along `facing` unless that is zero.
+pub fn drive_enemy(
+    delta: f32,
+    transform: &mut Transform,
+    controller: &mut KinematicCharacterController,
+    knockback: &mut Knockback,
+    velocity: Vec3,
+    facing: Vec3,
 ) {
-    commands.spawn(EnemyBundle {
-        enemy: Enemy {
-            move_timer: Timer::from_seconds(2.0, TimerMode::Repeating),
-            shoot_timer: Timer::from_seconds(1.5, TimerMode::Repeating),
-            current_direction: Vec3::ZERO,
+    // Exponential decay keeps the fade independent of the frame rate.
+    let decay = (-KNOCKBACK_DECAY * delta).exp();
+    **knockback *= decay;
+
+    controller.translation = Some((velocity + **knockback + Vec3::NEG_Y * FALL_SPEED) * delta);
+
+    if facing != Vec3::ZERO {
+        transform.look_to(facing, Vec3::Y);
+    }
+}
+
+/// A shot an enemy takes at the player, see `fire_at_player`.
+pub struct EnemyShot {
+    pub shooter: Entity,
+    pub team: Team,
+    /// Where the enemy stands.
+    pub position: Vec3,
+    pub player_position: Vec3,
+    pub player_velocity: Vec3,
+    /// Gravity the projectile falls with.
+    pub gravity: Vec3,
+}
+
+/// Fires a projectile of `enemy` at the player, and starts the wait for its next shot.
+pub fn fire_at_player(
+    commands: &mut Commands,
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<StandardMaterial>,
+    rules: &DamageRules,
+    enemy: &mut Enemy,
+    shot: EnemyShot,
+) {
+    let EnemyShot {
+        shooter,
+        team,
+        position,
+        player_position,
+        player_velocity,
+        gravity,
+    } = shot;
+    enemy.shoot_timer.reset();
+    let spawn_pos = position + Vec3::Y * 0.5; // Spawn slightly above
+
+    // Lead the player and arc over the drop, as well as the skill of the enemy allows.
+    let accuracy = enemy.accuracy.clamp(0.0, 1.0);
+    let aim = solve_ballistic_aim(
+        spawn_pos,
+        player_position,
+        player_velocity * accuracy,
+        enemy.projectile_speed,
+        gravity,
+        PROJECTILE_DAMPING,
+        PROJECTILE_LIFETIME,
+    )
+    // Out of reach, so just throw it straight at the player.
+    .unwrap_or(player_position - spawn_pos);
+    // The player is right where the projectile would spawn, so there is nowhere to aim.
+    let Some(aim) = aim.try_normalize() else {
+        return;
+    };
+    enemy.magazine = enemy.magazine.saturating_sub(1);
+    let aim = Quat::from_rotation_arc(Vec3::NEG_Z, aim);
+    let shoot_dir = scatter(aim, (1.0 - accuracy) * MAX_AIM_ERROR);
+    let velocity = shoot_dir * enemy.projectile_speed;
+
+    // Use the BallBundle from the previous step
+    commands.spawn((
+        BallBundle {
+            ball: Ball {
+                lifetime: Timer::from_seconds(PROJECTILE_LIFETIME, TimerMode::Once),
+            },
+            rigid_body: RigidBody::Dynamic,
+            mesh: Mesh3d(meshes.add(Sphere::new(0.3))),
+            material: MeshMaterial3d(materials.add(enemy.projectile_color)),
+            collider: Collider::ball(0.3),
+            restitution: Restitution::coefficient(0.8),
+            friction: Friction::coefficient(5.0),
+            damping: Damping {
+                linear_damping: PROJECTILE_DAMPING,
+                angular_damping: 0.5,
+            },
+            transform: Transform::from_translation(spawn_pos),
+            velocity: Velocity::linear(velocity),
         },
-        mesh: Mesh3d(meshes.add(Cuboid::from_size(Vec3::ONE))),
-        material: MeshMaterial3d(materials.add(Color::from(Srgba::BLUE))),
-        transform: Transform::from_xyz(5.0, 0.5, 5.0),
+        Projectile {
+            damage: enemy.projectile_damage,
+            knockback: enemy.projectile_knockback,
+        },
+        Owner(shooter),
+        team,
+        rules.projectile_groups(team),
+    ));
+}
+
+/// Spawns an enemy of `archetype` standing on `position`.
+pub fn spawn_enemy(
+    commands: &mut Commands,
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<StandardMaterial>,
+    handle: Handle<EnemyArchetype>,
+    archetype: &EnemyArchetype,
+    position: Vec3,
+    difficulty: Difficulty,
+) -> Entity {
+    let mut enemy = Enemy {
+        move_timer: Timer::from_seconds(archetype.move_interval, TimerMode::Repeating),
+        shoot_timer: Timer::from_seconds(archetype.shoot_interval, TimerMode::Once),
+        state_timer: Timer::from_seconds(IDLE_TIME, TimerMode::Once),
+        magazine: archetype.magazine_size,
+        ..default()
+    };
+    archetype.apply(&mut enemy, &difficulty);
+
+    let mut entity = commands.spawn(EnemyBundle {
+        enemy,
+        archetype: Archetype(handle),
+        state: EnemyState::default(),
+        perception: archetype.perception.clone(),
+        awareness: Awareness::default(),
+        nav_agent: NavAgent::default(),
+        knockback: Knockback::default(),
+        health: Health::new(archetype.max_health(&difficulty)),
+        difficulty,
+        team: Team::Enemy,
+        rigid_body: RigidBody::KinematicPositionBased,
+        controller: KinematicCharacterController {
+            offset: CharacterLength::Absolute(0.02),
+            snap_to_ground: Some(CharacterLength::Absolute(0.3)),
+            // Projectiles are pushed aside rather than blocking the way.
+            filter_groups: Some(CollisionGroups::new(
+                Team::Enemy.group(),
+                Group::ALL - PROJECTILE_GROUP,
+            )),
+            ..default()
+        },
+        collider: archetype.collider(),
+        collision_groups: CollisionGroups::new(Team::Enemy.group(), Group::ALL),
+        active_events: ActiveEvents::COLLISION_EVENTS,
+        mesh: Mesh3d(meshes.add(archetype.mesh())),
+        material: MeshMaterial3d(materials.add(archetype.color)),
+        // The origin of the enemy is the center of its cube.
+        transform: Transform::from_translation(position + Vec3::Y * archetype.size / 2.0),
     });
+    if let Some(tree) = &archetype.behavior_tree {
+        entity.insert(Behavior::new(tree.clone()));
+    }
+    entity.id()
 }
 
-pub fn handle_collisions(
-    mut collision_events: EventReader<CollisionEvent>,
-    projectile_query: Query<&Projectile>,
-    mut player_query: Query<&mut Health, With<Player>>,
+/// Starts the death sequence of enemies that ran out of health.
+pub fn kill_enemies(
+    mut commands: Commands,
+    mut death_events: EventReader<DeathEvent>,
+    mut cover: Option<ResMut<CoverPoints>>,
+    enemy_query: Query<(), (With<Enemy>, Without<Dying>)>,
 ) {
-    for collision_event in collision_events.read() {
-        if let CollisionEvent::Started(entity_a, entity_b, _) = collision_event {
-            // 1. Try to see if A is projectile and B is player
-            // 2. Try to see if B is projectile and A is player
-            let hit_data = if let Ok(p) = projectile_query.get(*entity_a) {
-                player_query.get_mut(*entity_b).map(|h| (h, p)).ok()
-            } else if let Ok(p) = projectile_query.get(*entity_b) {
-                player_query.get_mut(*entity_a).map(|h| (h, p)).ok()
-            } else {
-                None
-            };
-
-            // If we found a valid Player + Projectile pair, apply damage
-            if let Some((mut health, projectile)) = hit_data {
-                health.current -= projectile.damage;
-                info!("Player hit! Health remaining: {}", health.current);
+    for death in death_events.read() {
+        let entity = death.entity;
+        if enemy_query.contains(entity) {
+            info!("Enemy killed!");
+            if let Some(cover) = cover.as_deref_mut() {
+                cover.release(entity);
             }
+            // Without a collider the corpse can't soak up any more shots.
+            commands
+                .entity(entity)
+                .insert(Dying {
+                    timer: Timer::from_seconds(DEATH_DURATION, TimerMode::Once),
+                })
+                .remove::<(Collider, KinematicCharacterController)>();
+        }
+    }
+}
+
+/// Shrinks dying enemies down to nothing and despawns them afterwards.
+pub fn animate_dying(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut dying_query: Query<(Entity, &mut Transform, &mut Dying)>,
+) {
+    for (entity, mut transform, mut dying) in &mut dying_query {
+        dying.timer.tick(time.delta());
+        transform.scale = Vec3::splat(dying.timer.fraction_remaining());
+
+        if dying.timer.finished() {
+            commands.entity(entity).despawn();
         }
     }
 }
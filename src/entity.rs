@@ -1,18 +1,67 @@
-use bevy::prelude::Component;
+use bevy::prelude::{Component, Entity};
+use bevy_rapier3d::prelude::Group;
 
 #[derive(Debug, Component)]
 pub struct Player;
 
+/// Child of the player that carries the pitch of the view and the cameras.
+#[derive(Debug, Component)]
+pub struct PlayerHead;
+
+/// Hit points of anything that can be hurt.
+///
+/// Change it through `DamageEvent` and `HealEvent` rather than directly, so the change is
+/// clamped and subscribers like the HUD and the death handling hear about it.
 #[derive(Component)]
 pub struct Health {
     pub current: i32,
     pub max: i32,
 }
 
+impl Health {
+    /// Full health of `max` hit points.
+    pub fn new(max: i32) -> Self {
+        Self { current: max, max }
+    }
+
+    pub fn is_dead(&self) -> bool {
+        self.current <= 0
+    }
+
+    /// Health left as a fraction of the maximum, between 0 and 1.
+    pub fn fraction(&self) -> f32 {
+        (self.current as f32 / self.max as f32).clamp(0.0, 1.0)
+    }
+}
+
 #[derive(Component)]
 pub struct HealthBarFill; // Tag for the green part
 
 #[derive(Component)]
 pub struct Projectile {
     pub damage: i32,
+    /// Speed a hit adds to the target, pushing it away from where the projectile struck it,
+    /// in meters per second.
+    pub knockback: f32,
 }
+
+/// Side a combatant fights on. Projectiles carry the team of whoever fired them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Component)]
+pub enum Team {
+    Player,
+    Enemy,
+}
+
+impl Team {
+    /// Collision group the colliders of this team's combatants are members of.
+    pub fn group(self) -> Group {
+        match self {
+            Team::Player => Group::GROUP_2,
+            Team::Enemy => Group::GROUP_3,
+        }
+    }
+}
+
+/// The combatant that fired a projectile.
+#[derive(Debug, Clone, Copy, Component)]
+pub struct Owner(pub Entity);
@@ -0,0 +1,84 @@
+use bevy::prelude::*;
+use bevy::window::{CursorGrabMode, PrimaryWindow, WindowFocused};
+use bevy_rapier3d::prelude::*;
+
+use crate::input_map::Action;
+
+/// Whether the game is being played or waiting for the player to come back.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, States)]
+#[states(scoped_entities)]
+pub enum GameState {
+    #[default]
+    Playing,
+    /// Gameplay systems don't run and virtual time stands still, which also halts physics.
+    Paused,
+    /// The player died. Their input is ignored while the world goes on until they respawn.
+    Dead,
+}
+
+/// Locks and hides the cursor, so mouselook can't leave the window, and resumes time.
+pub fn grab_cursor(
+    mut window: Single<&mut Window, With<PrimaryWindow>>,
+    mut time: ResMut<Time<Virtual>>,
+    mut physics: Query<&mut RapierConfiguration>,
+) {
+    window.cursor_options.grab_mode = CursorGrabMode::Locked;
+    window.cursor_options.visible = false;
+    time.unpause();
+    for mut config in &mut physics {
+        config.physics_pipeline_active = true;
+    }
+}
+
+/// Gives the cursor back to the user, stops time and shows the pause overlay.
+pub fn release_cursor(
+    mut commands: Commands,
+    mut window: Single<&mut Window, With<PrimaryWindow>>,
+    mut time: ResMut<Time<Virtual>>,
+    mut physics: Query<&mut RapierConfiguration>,
+) {
+    window.cursor_options.grab_mode = CursorGrabMode::None;
+    window.cursor_options.visible = true;
+    time.pause();
+    for mut config in &mut physics {
+        config.physics_pipeline_active = false;
+    }
+
+    commands
+        .spawn((
+            StateScoped(GameState::Paused),
+            Node {
+                position_type: PositionType::Absolute,
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            BackgroundColor(Color::BLACK.with_alpha(0.5)),
+        ))
+        .with_child(Text::new("Paused\nClick to resume"));
+}
+
+/// Pauses on the pause action or when the window loses focus, and resumes on the pause or fire action.
+pub fn toggle_pause(
+    actions: Res<ButtonInput<Action>>,
+    mut focus_events: EventReader<WindowFocused>,
+    state: Res<State<GameState>>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    let lost_focus = focus_events.read().any(|event| !event.focused);
+
+    match state.get() {
+        GameState::Playing if lost_focus || actions.just_pressed(Action::Pause) => {
+            next_state.set(GameState::Paused);
+        }
+        GameState::Paused
+            if !lost_focus
+                && (actions.just_pressed(Action::Pause) || actions.just_pressed(Action::Fire)) =>
+        {
+            next_state.set(GameState::Playing);
+        }
+        _ => {}
+    }
+}
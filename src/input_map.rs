@@ -0,0 +1,249 @@
+use bevy::platform::collections::HashMap;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Logical actions the gameplay systems react to, independent of the physical input behind them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum Action {
+    MoveForward,
+    MoveBack,
+    MoveLeft,
+    MoveRight,
+    Jump,
+    Sprint,
+    Crouch,
+    Fire,
+    AltFire,
+    Reload,
+    /// Swaps the weapon in hand for the next one the player carries.
+    SwitchWeapon,
+    /// Narrows the FOV of the world model.
+    ZoomIn,
+    /// Widens the FOV of the world model.
+    ZoomOut,
+    Pause,
+}
+
+impl Action {
+    pub const ALL: [Action; 14] = [
+        Action::MoveForward,
+        Action::MoveBack,
+        Action::MoveLeft,
+        Action::MoveRight,
+        Action::Jump,
+        Action::Sprint,
+        Action::Crouch,
+        Action::Fire,
+        Action::AltFire,
+        Action::Reload,
+        Action::SwitchWeapon,
+        Action::ZoomIn,
+        Action::ZoomOut,
+        Action::Pause,
+    ];
+}
+
+/// A physical button that can trigger an [`Action`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Binding {
+    Key(KeyCode),
+    Mouse(MouseButton),
+    /// A button on any connected gamepad.
+    Gamepad(GamepadButton),
+}
+
+/// Maps every [`Action`] to the bindings that trigger it.
+///
+/// Gameplay systems never read the raw inputs. They read `ButtonInput<Action>` instead,
+/// which `update_actions` fills from this map every frame, so rebinding a control only
+/// means changing the bindings stored here.
+#[derive(Debug, Clone, Resource)]
+pub struct InputMap {
+    bindings: HashMap<Action, Vec<Binding>>,
+}
+
+impl Default for InputMap {
+    fn default() -> Self {
+        use Binding::*;
+
+        let mut input_map = Self {
+            bindings: HashMap::default(),
+        };
+
+        input_map
+            .bind(Action::MoveForward, Key(KeyCode::KeyW))
+            .bind(Action::MoveBack, Key(KeyCode::KeyS))
+            .bind(Action::MoveLeft, Key(KeyCode::KeyA))
+            .bind(Action::MoveRight, Key(KeyCode::KeyD))
+            .bind(Action::Jump, Key(KeyCode::Space))
+            .bind(Action::Jump, Gamepad(GamepadButton::South))
+            .bind(Action::Sprint, Key(KeyCode::ShiftLeft))
+            .bind(Action::Sprint, Gamepad(GamepadButton::LeftThumb))
+            .bind(Action::Crouch, Key(KeyCode::ControlLeft))
+            .bind(Action::Crouch, Gamepad(GamepadButton::East))
+            .bind(Action::Fire, Mouse(MouseButton::Left))
+            .bind(Action::Fire, Gamepad(GamepadButton::RightTrigger2))
+            .bind(Action::AltFire, Mouse(MouseButton::Right))
+            .bind(Action::AltFire, Gamepad(GamepadButton::LeftTrigger2))
+            .bind(Action::Reload, Key(KeyCode::KeyR))
+            .bind(Action::Reload, Gamepad(GamepadButton::West))
+            .bind(Action::SwitchWeapon, Key(KeyCode::KeyQ))
+            .bind(Action::SwitchWeapon, Gamepad(GamepadButton::North))
+            .bind(Action::ZoomIn, Key(KeyCode::ArrowUp))
+            .bind(Action::ZoomIn, Gamepad(GamepadButton::DPadUp))
+            .bind(Action::ZoomOut, Key(KeyCode::ArrowDown))
+            .bind(Action::ZoomOut, Gamepad(GamepadButton::DPadDown))
+            .bind(Action::Pause, Key(KeyCode::Escape))
+            .bind(Action::Pause, Gamepad(GamepadButton::Start));
+
+        input_map
+    }
+}
+
+impl InputMap {
+    /// Adds `binding` to the bindings of `action`, keeping the existing ones.
+    pub fn bind(&mut self, action: Action, binding: Binding) -> &mut Self {
+        let bindings = self.bindings.entry(action).or_default();
+        if !bindings.contains(&binding) {
+            bindings.push(binding);
+        }
+        self
+    }
+
+    /// Replaces all bindings of `action` with `bindings`.
+    pub fn rebind(&mut self, action: Action, bindings: impl IntoIterator<Item = Binding>) {
+        self.bindings.insert(action, bindings.into_iter().collect());
+    }
+
+    /// Removes `binding` from every action it is bound to.
+    pub fn unbind(&mut self, binding: Binding) {
+        for bindings in self.bindings.values_mut() {
+            bindings.retain(|bound| *bound != binding);
+        }
+    }
+
+    pub fn bindings(&self, action: Action) -> &[Binding] {
+        self.bindings.get(&action).map_or(&[], Vec::as_slice)
+    }
+
+    /// Iterates over every action together with its bindings.
+    pub fn iter(&self) -> impl Iterator<Item = (Action, &[Binding])> {
+        Action::ALL
+            .into_iter()
+            .map(|action| (action, self.bindings(action)))
+    }
+}
+
+/// Presses and releases the actions in `ButtonInput<Action>` based on the raw inputs.
+pub fn update_actions(
+    input_map: Res<InputMap>,
+    keys: Res<ButtonInput<KeyCode>>,
+    mouse_buttons: Res<ButtonInput<MouseButton>>,
+    gamepads: Query<&Gamepad>,
+    mut actions: ResMut<ButtonInput<Action>>,
+) {
+    // Forget last frame's `just_pressed` and `just_released`, but keep what is held down.
+    actions.clear();
+
+    for action in Action::ALL {
+        let pressed = input_map
+            .bindings(action)
+            .iter()
+            .any(|binding| match binding {
+                Binding::Key(key) => keys.pressed(*key),
+                Binding::Mouse(button) => mouse_buttons.pressed(*button),
+                Binding::Gamepad(button) => gamepads.iter().any(|gamepad| gamepad.pressed(*button)),
+            });
+
+        if pressed {
+            actions.press(action);
+        } else {
+            actions.release(action);
+        }
+    }
+}
+
+/// How raw stick positions are turned into movement and look input.
+#[derive(Debug, Clone, Resource)]
+pub struct StickSettings {
+    /// Stick deflection below which the stick counts as centered, between 0 and 1.
+    pub inner_deadzone: f32,
+    /// Stick deflection above which the stick counts as fully pushed, between 0 and 1.
+    pub outer_deadzone: f32,
+    /// Exponent of the response curve. Values above 1 give finer control near the center.
+    pub look_exponent: f32,
+    pub move_exponent: f32,
+}
+
+impl Default for StickSettings {
+    fn default() -> Self {
+        Self {
+            inner_deadzone: 0.15,
+            outer_deadzone: 0.95,
+            look_exponent: 2.0,
+            move_exponent: 1.0,
+        }
+    }
+}
+
+impl StickSettings {
+    /// Applies a radial deadzone and the response curve to a raw stick position.
+    ///
+    /// The deadzone is radial so that diagonals aren't cut off, and the remaining range
+    /// is stretched back to 0..1 so that the stick doesn't jump when leaving the deadzone.
+    pub fn shape(&self, raw: Vec2, exponent: f32) -> Vec2 {
+        let magnitude = raw.length();
+        if magnitude <= self.inner_deadzone {
+            return Vec2::ZERO;
+        }
+
+        let range = (self.outer_deadzone - self.inner_deadzone).max(f32::EPSILON);
+        let scaled = ((magnitude - self.inner_deadzone) / range).clamp(0.0, 1.0);
+        raw / magnitude * scaled.powf(exponent)
+    }
+}
+
+/// Analog input gathered from all devices, refreshed every frame by `update_axes`.
+#[derive(Debug, Default, Clone, Resource)]
+pub struct AxisInput {
+    /// Where the player wants to walk, with `y` pointing forward and `x` to the right.
+    /// Its length is at most 1, and less when a stick is only partly pushed.
+    pub movement: Vec2,
+    /// Right stick after shaping, with `x` turning right and `y` looking up.
+    /// This is a rate, so it still has to be multiplied by the time step.
+    pub look: Vec2,
+}
+
+/// Combines the movement actions and the sticks of all connected gamepads into `AxisInput`.
+pub fn update_axes(
+    actions: Res<ButtonInput<Action>>,
+    stick_settings: Res<StickSettings>,
+    gamepads: Query<&Gamepad>,
+    mut axes: ResMut<AxisInput>,
+) {
+    let mut keyboard = Vec2::ZERO;
+    if actions.pressed(Action::MoveForward) {
+        keyboard.y += 1.0;
+    }
+    if actions.pressed(Action::MoveBack) {
+        keyboard.y -= 1.0;
+    }
+    if actions.pressed(Action::MoveRight) {
+        keyboard.x += 1.0;
+    }
+    if actions.pressed(Action::MoveLeft) {
+        keyboard.x -= 1.0;
+    }
+
+    let mut left_stick = Vec2::ZERO;
+    let mut right_stick = Vec2::ZERO;
+    for gamepad in &gamepads {
+        left_stick += stick_settings.shape(gamepad.left_stick(), stick_settings.move_exponent);
+        right_stick += stick_settings.shape(gamepad.right_stick(), stick_settings.look_exponent);
+    }
+
+    // Keys are all or nothing, so they are normalized to keep diagonals from being faster,
+    // while a stick keeps its partial deflection for walking slowly.
+    axes.movement = (keyboard.normalize_or_zero() + left_stick).clamp_length_max(1.0);
+    axes.look = right_stick.clamp_length_max(1.0);
+}
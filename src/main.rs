@@ -23,6 +23,8 @@
 //!
 //! The `Player` is an entity holding two cameras, one for each model. The view model camera has a fixed
 //! FOV of 70 degrees, while the world model camera has a variable FOV that can be changed by the player.
+//! Both cameras sit on a `PlayerHead` child that pitches, while the `Player` itself only yaws and is moved
+//! through the world by a kinematic character controller.
 //!
 //! We use different `RenderLayers` to select what to render.
 //!
@@ -36,37 +38,126 @@
 //!
 //! ## Controls
 //!
+//! These are the default bindings. Gameplay systems read logical actions from `ButtonInput<Action>`,
+//! so every binding can be changed through the `InputMap` resource.
+//!
 //! | Key Binding          | Action        |
 //! |:---------------------|:--------------|
-//! | mouse                | Look around   |
+//! | mouse / right stick  | Look around   |
+//! | W / A / S / D        | Move          |
+//! | left stick           | Move          |
+//! | space                | Jump          |
+//! | left shift           | Sprint        |
+//! | left control         | Crouch/slide  |
 //! | arrow up             | Decrease FOV  |
 //! | arrow down           | Increase FOV  |
+//! | left mouse / RT      | Fire          |
+//! | right mouse          | Alt fire      |
+//! | R                    | Reload        |
+//! | escape               | Pause         |
 
+pub mod aiming;
+pub mod archetype;
 pub mod ball;
+pub mod behavior;
 pub mod clicker;
+pub mod cover;
 pub mod crosshair;
+pub mod damage;
 pub mod enemy;
 pub mod entity;
+pub mod game_state;
+pub mod input_map;
 pub mod movement;
+pub mod navigation;
+pub mod perception;
 pub mod render_layer;
+pub mod respawn;
 pub mod sensitivity;
+pub mod settings;
+pub mod sights;
+pub mod squad;
 pub mod view_model;
+pub mod waves;
+pub mod weapon;
 pub mod world_model;
 
+use archetype::EnemyArchetype;
+use archetype::EnemyArchetypeLoader;
+use archetype::load_archetypes;
+use archetype::reload_archetypes;
+use behavior::BehaviorTree;
+use behavior::BehaviorTreeLoader;
+use behavior::run_behaviors;
 use clicker::despawn_balls;
-use clicker::log_mouse_clicks;
+use cover::CoverPoints;
+use cover::CoverSettings;
+use cover::build_cover_points;
 use crosshair::spawn_crosshair;
+use crosshair::update_ammo_counter;
+use damage::DamageDealtEvent;
+use damage::DamageEvent;
+use damage::DamageRules;
+use damage::DeathEvent;
+use damage::HitEvent;
+use damage::HealEvent;
+use damage::ReviveEvent;
+use damage::apply_health_events;
+use damage::apply_knockback;
+use damage::handle_collisions;
+use enemy::animate_dying;
 use enemy::enemy_ai;
-use enemy::handle_collisions;
-use enemy::spawn_enemy;
+use enemy::kill_enemies;
+use enemy::update_enemy_state;
+use game_state::GameState;
+use game_state::grab_cursor;
+use game_state::release_cursor;
+use game_state::toggle_pause;
+use input_map::Action;
+use input_map::AxisInput;
+use input_map::InputMap;
+use input_map::StickSettings;
+use input_map::update_actions;
+use input_map::update_axes;
+use movement::MovementSettings;
 use movement::move_player;
+use movement::smooth_head_height;
 use movement::translate_player;
+use movement::update_grounded;
+use movement::update_movement_state;
+use navigation::NavGrid;
+use navigation::NavGridSettings;
+use navigation::build_nav_grid;
+use perception::perceive_player;
+use respawn::PlayerStats;
+use respawn::RespawnSettings;
+use respawn::kill_player;
+use respawn::respawn_player;
+use respawn::show_game_over;
+use respawn::track_stats;
+use settings::UserSettings;
+use settings::load_settings;
+use settings::save_settings;
+use sights::aim_down_sights;
+use squad::Squad;
+use squad::SquadSettings;
+use squad::update_squad;
 use view_model::spawn_view_model;
+use waves::WaveDirector;
+use waves::WaveSettings;
+use waves::run_waves;
+use waves::spawn_wave_counter;
+use waves::update_wave_counter;
+use weapon::GunshotEvent;
+use weapon::fade_tracers;
+use weapon::fire_weapon;
+use weapon::switch_weapon;
 use world_model::change_fov;
 use world_model::spawn_lights;
 use world_model::spawn_text;
 use world_model::spawn_world_model;
 
+use bevy::input::InputSystem;
 use bevy::prelude::*;
 use bevy_rapier3d::prelude::*;
 
@@ -76,6 +167,38 @@ fn main() {
     App::new()
         .add_plugins(DefaultPlugins)
         .add_plugins(RapierPhysicsPlugin::<NoUserData>::default())
+        .init_state::<GameState>()
+        .init_resource::<MovementSettings>()
+        .init_resource::<InputMap>()
+        .init_resource::<ButtonInput<Action>>()
+        .init_resource::<StickSettings>()
+        .init_resource::<AxisInput>()
+        .init_resource::<UserSettings>()
+        .init_resource::<DamageRules>()
+        .add_event::<HitEvent>()
+        .add_event::<DamageEvent>()
+        .add_event::<DamageDealtEvent>()
+        .add_event::<HealEvent>()
+        .add_event::<ReviveEvent>()
+        .add_event::<DeathEvent>()
+        .add_event::<GunshotEvent>()
+        .init_resource::<RespawnSettings>()
+        .init_resource::<PlayerStats>()
+        .init_resource::<NavGridSettings>()
+        .init_resource::<WaveSettings>()
+        .init_resource::<WaveDirector>()
+        .init_resource::<SquadSettings>()
+        .init_resource::<Squad>()
+        .init_resource::<CoverSettings>()
+        .init_asset::<EnemyArchetype>()
+        .init_asset_loader::<EnemyArchetypeLoader>()
+        .init_asset::<BehaviorTree>()
+        .init_asset_loader::<BehaviorTreeLoader>()
+        .add_systems(PreStartup, load_settings)
+        .add_systems(
+            PreUpdate,
+            (update_actions, update_axes).chain().after(InputSystem),
+        )
         .add_systems(
             Startup,
             (
@@ -84,21 +207,81 @@ fn main() {
                 spawn_lights,
                 spawn_text,
                 spawn_crosshair,
-                spawn_enemy,
+                spawn_wave_counter,
+                load_archetypes,
+            ),
+        )
+        .add_systems(OnEnter(GameState::Playing), grab_cursor)
+        .add_systems(OnEnter(GameState::Paused), release_cursor)
+        .add_systems(OnEnter(GameState::Dead), show_game_over)
+        .add_systems(
+            Update,
+            (
+                toggle_pause,
+                (change_fov, aim_down_sights).chain(),
+                (
+                    handle_collisions,
+                    (apply_knockback, apply_health_events),
+                    (update_health_bar, track_stats, kill_player),
+                )
+                    .chain(),
+                update_ammo_counter,
+                update_wave_counter,
+                reload_archetypes,
             ),
         )
         .add_systems(
             Update,
             (
                 move_player,
-                translate_player,
-                change_fov,
+                (
+                    update_grounded,
+                    update_movement_state,
+                    translate_player,
+                    smooth_head_height,
+                )
+                    .chain(),
+                (switch_weapon, fire_weapon).chain(),
+                run_waves,
+            )
+                .run_if(in_state(GameState::Playing)),
+        )
+        // The world goes on while the player is dead.
+        .add_systems(
+            Update,
+            (
                 despawn_balls,
-                log_mouse_clicks,
-                enemy_ai,
-                handle_collisions,
-                update_health_bar,
+                fade_tracers,
+                (
+                    perceive_player,
+                    update_squad,
+                    update_enemy_state,
+                    (enemy_ai, run_behaviors),
+                )
+                    .chain(),
+                kill_enemies,
+                animate_dying,
+            )
+                .run_if(not(in_state(GameState::Paused))),
+        )
+        // The revive has to reach `apply_health_events` in the same frame.
+        .add_systems(
+            Update,
+            respawn_player
+                .before(apply_health_events)
+                .run_if(in_state(GameState::Dead)),
+        )
+        .add_systems(
+            PostUpdate,
+            (
+                build_nav_grid
+                    .after(PhysicsSet::Writeback)
+                    .run_if(not(resource_exists::<NavGrid>)),
+                build_cover_points.run_if(
+                    resource_exists::<NavGrid>.and(not(resource_exists::<CoverPoints>)),
+                ),
             ),
         )
+        .add_systems(Last, save_settings)
         .run();
 }
@@ -1,30 +1,178 @@
 use bevy::{input::mouse::AccumulatedMouseMotion, prelude::*};
+use bevy_rapier3d::prelude::*;
 
 use std::f32::consts::FRAC_PI_2;
 
-use crate::entity::Player;
+use crate::entity::{Player, PlayerHead};
+use crate::input_map::{Action, AxisInput};
 use crate::sensitivity::CameraSensitivity;
+use crate::view_model::EYE_OFFSET;
+
+/// Half the width and depth of the player's collider.
+const PLAYER_HALF_WIDTH: f32 = 0.5;
+
+/// How quickly the head follows the eye height of the current movement state.
+const HEAD_HEIGHT_SMOOTHING: f32 = 12.0;
+
+/// Movement parameters that differ between the states of [`MovementState`].
+#[derive(Debug, Clone, Copy)]
+pub struct StateSettings {
+    /// Horizontal speed the player accelerates towards, in meters per second.
+    /// While sliding this only caps the momentum gained on slopes.
+    pub speed: f32,
+    /// Ground acceleration as a fraction of `speed` gained per second, like `sv_accelerate` in Quake.
+    pub acceleration: f32,
+    /// How quickly the ground slows the player down, like `sv_friction` in Quake.
+    pub friction: f32,
+    /// Half the height of the player's collider.
+    pub half_height: f32,
+}
+
+/// Tunable parameters of the player's movement.
+///
+/// Horizontal movement follows the Quake model: the ground applies friction and then accelerates
+/// the player along the direction of the movement keys, while the air only accelerates.
+#[derive(Debug, Resource)]
+pub struct MovementSettings {
+    pub walk: StateSettings,
+    pub sprint: StateSettings,
+    pub crouch: StateSettings,
+    pub slide: StateSettings,
+    /// Acceleration while airborne, like `sv_airaccelerate` in Quake.
+    pub air_acceleration: f32,
+    /// Below this speed friction acts as if the player moved this fast, so that it comes to a
+    /// full stop instead of creeping along, in meters per second.
+    pub stop_speed: f32,
+    /// Minimum horizontal speed needed to start a slide instead of crouching, in meters per second.
+    pub slide_min_speed: f32,
+    /// A slide turns into a crouch once it drops below this speed, in meters per second.
+    pub slide_stop_speed: f32,
+    /// Downward acceleration applied while airborne, in meters per second squared.
+    pub gravity: f32,
+    /// How high a jump lifts the feet of the player, in meters.
+    pub jump_height: f32,
+    /// How long after walking off a ledge the player is still allowed to jump, in seconds.
+    pub coyote_time: f32,
+    /// How long a jump press is remembered before landing, in seconds.
+    pub jump_buffer_time: f32,
+}
+
+impl Default for MovementSettings {
+    fn default() -> Self {
+        Self {
+            walk: StateSettings {
+                speed: 6.0,
+                acceleration: 10.0,
+                friction: 6.0,
+                half_height: 1.0,
+            },
+            sprint: StateSettings {
+                speed: 9.5,
+                acceleration: 8.0,
+                friction: 6.0,
+                half_height: 1.0,
+            },
+            crouch: StateSettings {
+                speed: 3.0,
+                acceleration: 10.0,
+                friction: 6.0,
+                half_height: 0.6,
+            },
+            // Sliding ignores the movement keys and barely loses speed.
+            slide: StateSettings {
+                speed: 16.0,
+                acceleration: 0.0,
+                friction: 0.5,
+                half_height: 0.6,
+            },
+            air_acceleration: 2.0,
+            stop_speed: 1.5,
+            slide_min_speed: 7.5,
+            slide_stop_speed: 3.0,
+            gravity: 9.81,
+            // High enough to get from the lower cube onto the top cube.
+            jump_height: 1.6,
+            coyote_time: 0.1,
+            jump_buffer_time: 0.15,
+        }
+    }
+}
+
+impl MovementSettings {
+    pub fn state(&self, state: MovementState) -> &StateSettings {
+        match state {
+            MovementState::Walk => &self.walk,
+            MovementState::Sprint => &self.sprint,
+            MovementState::Crouch => &self.crouch,
+            MovementState::Slide => &self.slide,
+        }
+    }
+}
+
+/// What the player's legs are currently doing.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Component)]
+pub enum MovementState {
+    #[default]
+    Walk,
+    Sprint,
+    Crouch,
+    /// Keeps the momentum of a sprint while crouched, and gains speed down slopes.
+    Slide,
+}
+
+/// Builds the collider of the player for the given half height.
+pub fn player_collider(half_height: f32) -> Collider {
+    Collider::cuboid(PLAYER_HALF_WIDTH, half_height, PLAYER_HALF_WIDTH)
+}
+
+/// Velocity the character controller moves the player with, in meters per second.
+#[derive(Debug, Default, Component, Deref, DerefMut)]
+pub struct PlayerVelocity(pub Vec3);
+
+/// Marks a character controller that was standing on the ground after the last physics step.
+#[derive(Debug, Component)]
+pub struct Grounded;
+
+/// Bookkeeping for coyote time and jump buffering.
+#[derive(Debug, Default, Component)]
+pub struct JumpState {
+    /// Seconds since the player last stood on the ground.
+    pub since_grounded: f32,
+    /// Seconds since jump was pressed, or `None` if there is no pending jump.
+    pub since_jump_pressed: Option<f32>,
+}
 
 pub fn move_player(
+    time: Res<Time>,
     accumulated_mouse_motion: Res<AccumulatedMouseMotion>,
+    axes: Res<AxisInput>,
     player: Single<(&mut Transform, &CameraSensitivity), With<Player>>,
+    head: Single<&mut Transform, (With<PlayerHead>, Without<Player>)>,
 ) {
     let (mut transform, camera_sensitivity) = player.into_inner();
+    let mut head_transform = head.into_inner();
 
     let delta = accumulated_mouse_motion.delta;
+    let stick = axes.look;
 
-    if delta != Vec2::ZERO {
+    if delta != Vec2::ZERO || stick != Vec2::ZERO {
         // Note that we are not multiplying by delta_time here.
         // The reason is that for mouse movement, we already get the full movement that happened since the last frame.
         // This means that if we multiply by delta_time, we will get a smaller rotation than intended by the user.
-        // This situation is reversed when reading e.g. analog input from a gamepad however, where the same rules
-        // as for keyboard input apply. Such an input should be multiplied by delta_time to get the intended rotation
-        // independent of the framerate.
-        let delta_yaw = -delta.x * camera_sensitivity.x;
-        let delta_pitch = -delta.y * camera_sensitivity.y;
+        let mut delta_yaw = -delta.x * camera_sensitivity.mouse.x;
+        let mut delta_pitch = -delta.y * camera_sensitivity.mouse.y;
+
+        // This situation is reversed when reading analog input from a gamepad however, where the same rules
+        // as for keyboard input apply. The stick only tells us how fast to turn, so it is multiplied by delta_time
+        // to get the intended rotation independent of the framerate.
+        delta_yaw -= stick.x * camera_sensitivity.gamepad.x * time.delta_secs();
+        delta_pitch += stick.y * camera_sensitivity.gamepad.y * time.delta_secs();
 
-        let (yaw, pitch, roll) = transform.rotation.to_euler(EulerRot::YXZ);
-        let yaw = yaw + delta_yaw;
+        // The yaw turns the whole player, while the pitch only tilts the head.
+        // This keeps the collider of the player upright no matter where we look.
+        transform.rotate_y(delta_yaw);
+
+        let (_, pitch, roll) = head_transform.rotation.to_euler(EulerRot::YXZ);
 
         // If the pitch was ±¹⁄₂ π, the camera would look straight up or down.
         // When the user wants to move the camera back to the horizon, which way should the camera face?
@@ -35,35 +183,305 @@ pub fn move_player(
         const PITCH_LIMIT: f32 = FRAC_PI_2 - 0.01;
         let pitch = (pitch + delta_pitch).clamp(-PITCH_LIMIT, PITCH_LIMIT);
 
-        transform.rotation = Quat::from_euler(EulerRot::YXZ, yaw, pitch, roll);
+        head_transform.rotation = Quat::from_euler(EulerRot::YXZ, 0.0, pitch, roll);
+    }
+}
+
+/// Slows `velocity` down the way Quake's ground friction does.
+fn apply_friction(velocity: Vec3, friction: f32, stop_speed: f32, delta_secs: f32) -> Vec3 {
+    let speed = velocity.length();
+    if speed < f32::EPSILON {
+        return Vec3::ZERO;
+    }
+
+    // Slow movement is braked as if it was at `stop_speed`, so it ends instead of decaying forever.
+    let control = speed.max(stop_speed);
+    let new_speed = (speed - control * friction * delta_secs).max(0.0);
+    velocity * (new_speed / speed)
+}
+
+/// Accelerates `velocity` along `wish_direction` the way Quake does.
+///
+/// Only the speed along `wish_direction` is capped at `wish_speed`, which is what makes
+/// strafing in the air possible.
+fn accelerate(
+    velocity: Vec3,
+    wish_direction: Vec3,
+    wish_speed: f32,
+    acceleration: f32,
+    delta_secs: f32,
+) -> Vec3 {
+    let current_speed = velocity.dot(wish_direction);
+    let add_speed = wish_speed - current_speed;
+    if add_speed <= 0.0 {
+        return velocity;
+    }
+
+    let acceleration_speed = (acceleration * wish_speed * delta_secs).min(add_speed);
+    velocity + wish_direction * acceleration_speed
+}
+
+/// Derives `Grounded` from the ground probes the character controller ran during the last physics step.
+pub fn update_grounded(
+    mut commands: Commands,
+    query: Query<(Entity, &KinematicCharacterControllerOutput, Has<Grounded>)>,
+) {
+    for (entity, output, was_grounded) in &query {
+        if output.grounded && !was_grounded {
+            commands.entity(entity).insert(Grounded);
+        } else if !output.grounded && was_grounded {
+            commands.entity(entity).remove::<Grounded>();
+        }
+    }
+}
+
+/// Moves the player between walking, sprinting, crouching and sliding,
+/// and resizes its collider to match the new state.
+pub fn update_movement_state(
+    settings: Res<MovementSettings>,
+    actions: Res<ButtonInput<Action>>,
+    axes: Res<AxisInput>,
+    rapier_context: ReadRapierContext,
+    player: Single<
+        (
+            Entity,
+            &mut MovementState,
+            &mut Transform,
+            &mut Collider,
+            &PlayerVelocity,
+            Has<Grounded>,
+        ),
+        With<Player>,
+    >,
+    head: Single<&mut Transform, (With<PlayerHead>, Without<Player>)>,
+) {
+    let (entity, mut state, mut transform, mut collider, velocity, grounded) = player.into_inner();
+    let mut head_transform = head.into_inner();
+
+    // Sprinting only makes sense while mostly heading forward.
+    let sprinting = actions.pressed(Action::Sprint) && axes.movement.y > 0.5;
+    let crouching = actions.pressed(Action::Crouch);
+    let speed = velocity.xz().length();
+
+    // Standing up needs room above the head for the full collider.
+    let has_headroom = || {
+        let Ok(context) = rapier_context.single() else {
+            return true;
+        };
+        let current_half_height = settings.state(*state).half_height;
+        let standing_half_height = settings.walk.half_height;
+        let feet = transform.translation.y - current_half_height;
+        // Shrink the probe a little so it doesn't touch the floor or the walls we lean against.
+        let probe = Collider::cuboid(
+            PLAYER_HALF_WIDTH - 0.05,
+            standing_half_height - 0.05,
+            PLAYER_HALF_WIDTH - 0.05,
+        );
+        let probe_position = transform
+            .translation
+            .with_y(feet + standing_half_height + 0.05);
+        let mut blocked = false;
+        context.intersections_with_shape(
+            probe_position,
+            transform.rotation,
+            &probe,
+            QueryFilter::exclude_dynamic().exclude_rigid_body(entity),
+            |_| {
+                blocked = true;
+                false
+            },
+        );
+        !blocked
+    };
+
+    let next = match *state {
+        MovementState::Walk | MovementState::Sprint
+            if crouching && grounded && speed >= settings.slide_min_speed =>
+        {
+            MovementState::Slide
+        }
+        MovementState::Walk | MovementState::Sprint if crouching => MovementState::Crouch,
+        MovementState::Walk | MovementState::Sprint if sprinting => MovementState::Sprint,
+        MovementState::Walk | MovementState::Sprint => MovementState::Walk,
+        MovementState::Slide if crouching && speed >= settings.slide_stop_speed => {
+            MovementState::Slide
+        }
+        MovementState::Crouch | MovementState::Slide if crouching || !has_headroom() => {
+            MovementState::Crouch
+        }
+        MovementState::Crouch | MovementState::Slide => MovementState::Walk,
+    };
+
+    if next == *state {
+        return;
+    }
+
+    let old_half_height = settings.state(*state).half_height;
+    let new_half_height = settings.state(next).half_height;
+    *state = next;
+
+    if old_half_height != new_half_height {
+        // Keep the feet where they are, so crouching lowers the head instead of lifting the feet.
+        let shift = old_half_height - new_half_height;
+        *collider = player_collider(new_half_height);
+        transform.translation.y -= shift;
+        // Undo the jump of the head, so `smooth_head_height` can ease it to its new height.
+        head_transform.translation.y += shift;
     }
 }
 
+/// Eases the head towards the eye height of the current movement state.
+pub fn smooth_head_height(
+    time: Res<Time>,
+    settings: Res<MovementSettings>,
+    player: Single<&MovementState, With<Player>>,
+    head: Single<&mut Transform, With<PlayerHead>>,
+) {
+    let target = settings.state(**player).half_height - EYE_OFFSET;
+    let mut head_transform = head.into_inner();
+
+    // Exponential decay keeps the easing independent of the frame rate.
+    let blend = 1.0 - (-HEAD_HEIGHT_SMOOTHING * time.delta_secs()).exp();
+    head_transform.translation.y = head_transform.translation.y.lerp(target, blend);
+}
+
 pub fn translate_player(
-    input: Res<ButtonInput<KeyCode>>,
-    player: Single<(&mut Transform, &CameraSensitivity), With<Player>>,
+    time: Res<Time>,
+    settings: Res<MovementSettings>,
+    actions: Res<ButtonInput<Action>>,
+    axes: Res<AxisInput>,
+    rapier_context: ReadRapierContext,
+    player: Single<
+        (
+            Entity,
+            &Transform,
+            &MovementState,
+            &mut PlayerVelocity,
+            &mut JumpState,
+            Has<Grounded>,
+        ),
+        With<Player>,
+    >,
+    controller: Single<
+        (
+            &mut KinematicCharacterController,
+            Option<&KinematicCharacterControllerOutput>,
+        ),
+        With<Player>,
+    >,
 ) {
-    let (mut transform, _) = player.into_inner();
+    let (entity, transform, state, mut velocity, mut jump, grounded) = player.into_inner();
+    let (mut controller, output) = controller.into_inner();
+    let delta_secs = time.delta_secs();
+    let state_settings = settings.state(*state);
+
+    // The player only ever yaws, so its forward and right vectors stay on the XZ plane.
+    let wish = *transform.forward() * axes.movement.y + *transform.right() * axes.movement.x;
+    let wish_direction = wish.normalize_or_zero();
+    // A partly pushed stick walks slower than the full speed of the state.
+    let wish_speed = state_settings.speed * wish.length();
 
-    let (yaw, _, _) = transform.rotation.to_euler(EulerRot::YXZ);
+    let mut horizontal = velocity.with_y(0.0);
 
-    if input.pressed(KeyCode::KeyW) {
-        transform.translation.x = transform.translation.x - yaw.sin() * 0.3;
-        transform.translation.z = transform.translation.z - yaw.cos() * 0.3;
+    if grounded {
+        horizontal = apply_friction(
+            horizontal,
+            state_settings.friction,
+            settings.stop_speed,
+            delta_secs,
+        );
+        horizontal = accelerate(
+            horizontal,
+            wish_direction,
+            wish_speed,
+            state_settings.acceleration,
+            delta_secs,
+        );
+    } else if *state != MovementState::Slide {
+        // A slide ignores the movement keys, even after jumping out of it.
+        horizontal = accelerate(
+            horizontal,
+            wish_direction,
+            wish_speed,
+            settings.air_acceleration,
+            delta_secs,
+        );
     }
 
-    if input.pressed(KeyCode::KeyA) {
-        transform.translation.x = transform.translation.x - yaw.cos() * 0.3;
-        transform.translation.z = transform.translation.z + yaw.sin() * 0.3;
+    if *state == MovementState::Slide && grounded {
+        // Slopes pull a slide downhill and keep its momentum going.
+        let ground_normal = rapier_context.single().ok().and_then(|context| {
+            let feet = transform.translation - Vec3::Y * state_settings.half_height;
+            context
+                .cast_ray_and_get_normal(
+                    feet + Vec3::Y * 0.1,
+                    Vec3::NEG_Y,
+                    0.5,
+                    true,
+                    QueryFilter::exclude_dynamic().exclude_rigid_body(entity),
+                )
+                .map(|(_, intersection)| intersection.normal)
+        });
+        if let Some(normal) = ground_normal {
+            // The part of gravity that runs along the slope.
+            let downhill = Vec3::NEG_Y - normal * Vec3::NEG_Y.dot(normal);
+            horizontal += downhill.with_y(0.0) * settings.gravity * delta_secs;
+        }
+
+        horizontal = horizontal.clamp_length_max(state_settings.speed);
     }
 
-    if input.pressed(KeyCode::KeyD) {
-        transform.translation.x = transform.translation.x + yaw.cos() * 0.3;
-        transform.translation.z = transform.translation.z - yaw.sin() * 0.3;
+    velocity.x = horizontal.x;
+    velocity.z = horizontal.z;
+
+    // Standing on the ground cancels any downward velocity we gathered while falling.
+    if grounded {
+        jump.since_grounded = 0.0;
+        velocity.y = velocity.y.max(0.0);
+    } else {
+        jump.since_grounded += delta_secs;
+        velocity.y -= settings.gravity * delta_secs;
     }
 
-    if input.pressed(KeyCode::KeyS) {
-        transform.translation.x = transform.translation.x + yaw.sin() * 0.3;
-        transform.translation.z = transform.translation.z + yaw.cos() * 0.3;
+    // Bumping the head on a ceiling ends the ascent right away instead of sticking to it.
+    if let Some(output) = output
+        && velocity.y > 0.0
+        && output.effective_translation.y < output.desired_translation.y * 0.5
+    {
+        velocity.y = 0.0;
     }
+
+    // A press is buffered for a short while, so pressing jump right before landing still counts.
+    if actions.just_pressed(Action::Jump) {
+        jump.since_jump_pressed = Some(0.0);
+    } else if let Some(since_jump_pressed) = jump.since_jump_pressed.as_mut() {
+        *since_jump_pressed += delta_secs;
+    }
+    if jump
+        .since_jump_pressed
+        .is_some_and(|since| since > settings.jump_buffer_time)
+    {
+        jump.since_jump_pressed = None;
+    }
+
+    // Coyote time lets the player jump for a moment after walking off a ledge.
+    let can_jump = jump.since_grounded <= settings.coyote_time && velocity.y <= 0.0;
+    if can_jump && jump.since_jump_pressed.is_some() {
+        // Solve v² = 2gh for the take-off speed that reaches the configured height.
+        velocity.y = (2.0 * settings.gravity * settings.jump_height).sqrt();
+        jump.since_jump_pressed = None;
+        // Spend the coyote time, so the same ledge can't be jumped off twice.
+        jump.since_grounded = f32::INFINITY;
+    }
+
+    // Gravity keeps pulling while grounded, so the controller keeps probing for the floor.
+    let probe = if grounded && velocity.y <= 0.0 {
+        Vec3::NEG_Y * settings.gravity * delta_secs
+    } else {
+        Vec3::ZERO
+    };
+
+    // The controller resolves this translation against the world during the next physics step,
+    // sliding along walls and stepping up small ledges.
+    controller.translation = Some((**velocity + probe) * delta_secs);
 }
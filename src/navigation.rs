@@ -0,0 +1,466 @@
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+
+use crate::damage::PROJECTILE_GROUP;
+use crate::entity::Team;
+
+/// Distance the destination of a `NavAgent` has to move before its path is planned again.
+const REPATH_DISTANCE: f32 = 1.0;
+
+/// Horizontal distance at which a waypoint counts as reached.
+const WAYPOINT_RADIUS: f32 = 0.3;
+
+/// Horizontal distance at which an agent counts as standing on its destination.
+const ARRIVAL_RADIUS: f32 = 0.5;
+
+/// Cells searched around a blocked cell for the closest walkable one.
+const MAX_SNAP_CELLS: i32 = 8;
+
+/// Shape of the area `build_nav_grid` covers and of the agents walking it.
+#[derive(Debug, Clone, Resource)]
+pub struct NavGridSettings {
+    /// Area covered by the grid on the XZ plane, `y` of the rect being world `z`.
+    pub bounds: Rect,
+    /// Edge length of a cell, in meters.
+    pub cell_size: f32,
+    /// Half of the width of the widest agent, so paths keep it clear of walls.
+    pub agent_radius: f32,
+    /// Height of the tallest agent. Obstacles higher above the ground than this can be walked under.
+    pub agent_height: f32,
+    /// Height above the floor up to which ground still counts as floor. Anything taller is an obstacle.
+    pub max_step: f32,
+}
+
+impl Default for NavGridSettings {
+    fn default() -> Self {
+        Self {
+            // The whole floor spawned by `spawn_world_model`.
+            bounds: Rect::new(-100.0, -100.0, 100.0, 100.0),
+            cell_size: 1.0,
+            agent_radius: 0.5,
+            agent_height: 1.0,
+            max_step: 0.3,
+        }
+    }
+}
+
+/// Which cells of the floor an enemy can stand on, built from the fixed colliders of the world.
+#[derive(Debug, Clone, Resource)]
+pub struct NavGrid {
+    /// World position of the corner of cell `(0, 0)` on the XZ plane.
+    origin: Vec2,
+    cell_size: f32,
+    width: i32,
+    depth: i32,
+    walkable: Vec<bool>,
+}
+
+impl NavGrid {
+    /// The cell containing `position`, which may lie outside the grid.
+    fn cell_at(&self, position: Vec3) -> IVec2 {
+        ((position.xz() - self.origin) / self.cell_size)
+            .floor()
+            .as_ivec2()
+    }
+
+    /// The cell of the grid closest to `cell`.
+    fn clamp_cell(&self, cell: IVec2) -> IVec2 {
+        cell.clamp(IVec2::ZERO, IVec2::new(self.width - 1, self.depth - 1))
+    }
+
+    /// World position of the center of `cell`, on the ground.
+    fn center(&self, cell: IVec2) -> Vec3 {
+        let center = self.origin + (cell.as_vec2() + 0.5) * self.cell_size;
+        Vec3::new(center.x, 0.0, center.y)
+    }
+
+    fn index(&self, cell: IVec2) -> usize {
+        (cell.y * self.width + cell.x) as usize
+    }
+
+    fn contains(&self, cell: IVec2) -> bool {
+        cell.x >= 0 && cell.y >= 0 && cell.x < self.width && cell.y < self.depth
+    }
+
+    fn is_walkable_cell(&self, cell: IVec2) -> bool {
+        self.contains(cell) && self.walkable[self.index(cell)]
+    }
+
+    pub fn is_walkable(&self, position: Vec3) -> bool {
+        self.is_walkable_cell(self.cell_at(position))
+    }
+
+    /// The walkable cell closest to `cell`, searching outwards ring by ring.
+    fn nearest_walkable_cell(&self, cell: IVec2) -> Option<IVec2> {
+        (0..=MAX_SNAP_CELLS).find_map(|ring| {
+            (-ring..=ring)
+                .flat_map(|x| (-ring..=ring).map(move |z| IVec2::new(x, z)))
+                .filter(|offset| offset.x.abs() == ring || offset.y.abs() == ring)
+                .map(|offset| cell + offset)
+                .filter(|&candidate| self.is_walkable_cell(candidate))
+                .min_by_key(|candidate| (*candidate - cell).length_squared())
+        })
+    }
+
+    /// The center of the walkable cell closest to `position`. Positions outside the grid
+    /// are brought back onto it first.
+    pub fn nearest_walkable(&self, position: Vec3) -> Option<Vec3> {
+        if self.is_walkable(position) {
+            return Some(position);
+        }
+        self.nearest_walkable_cell(self.clamp_cell(self.cell_at(position)))
+            .map(|cell| self.center(cell))
+    }
+
+    /// Plans a path from `from` to `to` with A*, moving between the eight neighbours of a cell.
+    ///
+    /// Ends that are blocked or off the grid are moved to the closest walkable cell. The returned waypoints
+    /// don't include the start, and are empty if `to` can't be reached.
+    pub fn find_path(&self, from: Vec3, to: Vec3) -> Vec<Vec3> {
+        let (Some(start), Some(goal)) = (
+            self.nearest_walkable_cell(self.clamp_cell(self.cell_at(from))),
+            self.nearest_walkable_cell(self.clamp_cell(self.cell_at(to))),
+        ) else {
+            return Vec::new();
+        };
+
+        let heuristic = |cell: IVec2| {
+            // Octile distance, the exact cost on an empty grid with diagonal moves.
+            let delta = (goal - cell).abs();
+            let (long, short) = (delta.max_element() as f32, delta.min_element() as f32);
+            long + (std::f32::consts::SQRT_2 - 1.0) * short
+        };
+
+        let cells = self.walkable.len();
+        let mut cost = vec![f32::INFINITY; cells];
+        let mut came_from = vec![None; cells];
+        let mut open = BinaryHeap::new();
+        cost[self.index(start)] = 0.0;
+        open.push(OpenCell {
+            estimate: heuristic(start),
+            cell: start,
+        });
+
+        while let Some(OpenCell { estimate, cell }) = open.pop() {
+            if cell == goal {
+                break;
+            }
+            let cell_cost = cost[self.index(cell)];
+            // Skip entries that were superseded by a cheaper way to the same cell.
+            if estimate > cell_cost + heuristic(cell) {
+                continue;
+            }
+
+            for x in -1..=1 {
+                for z in -1..=1 {
+                    let step = IVec2::new(x, z);
+                    let neighbour = cell + step;
+                    if step == IVec2::ZERO || !self.is_walkable_cell(neighbour) {
+                        continue;
+                    }
+                    // Don't cut corners, the agent would clip the obstacle.
+                    if x != 0
+                        && z != 0
+                        && (!self.is_walkable_cell(cell + IVec2::new(x, 0))
+                            || !self.is_walkable_cell(cell + IVec2::new(0, z)))
+                    {
+                        continue;
+                    }
+
+                    let neighbour_cost = cell_cost + step.as_vec2().length();
+                    let index = self.index(neighbour);
+                    if neighbour_cost < cost[index] {
+                        cost[index] = neighbour_cost;
+                        came_from[index] = Some(cell);
+                        open.push(OpenCell {
+                            estimate: neighbour_cost + heuristic(neighbour),
+                            cell: neighbour,
+                        });
+                    }
+                }
+            }
+        }
+
+        if start != goal && came_from[self.index(goal)].is_none() {
+            return Vec::new();
+        }
+
+        let mut path = Vec::new();
+        let mut cell = goal;
+        while cell != start {
+            path.push(self.center(cell));
+            cell = came_from[self.index(cell)].expect("every cell on the path has a predecessor");
+        }
+        path.reverse();
+
+        // End exactly on the destination when it can be stood on, not on the center of its cell.
+        if self.is_walkable(to) {
+            match path.last_mut() {
+                Some(last) => *last = to,
+                None => path.push(to),
+            }
+        }
+        path
+    }
+}
+
+/// Entry of the A* open list, ordered so the `BinaryHeap` pops the lowest estimate first.
+struct OpenCell {
+    estimate: f32,
+    cell: IVec2,
+}
+
+impl PartialEq for OpenCell {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for OpenCell {}
+
+impl PartialOrd for OpenCell {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for OpenCell {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.estimate.total_cmp(&self.estimate)
+    }
+}
+
+/// Follows paths planned on the `NavGrid` towards a destination.
+#[derive(Debug, Default, Clone, Component)]
+pub struct NavAgent {
+    /// Where the agent is heading, or `None` when it stands still.
+    pub destination: Option<Vec3>,
+    /// Waypoints left to the destination.
+    pub path: Vec<Vec3>,
+}
+
+impl NavAgent {
+    /// Heads for `destination`, planning a new path unless it is close to the current one.
+    ///
+    /// Without a grid the agent walks straight at the destination.
+    pub fn go_to(&mut self, destination: Vec3, position: Vec3, grid: Option<&NavGrid>) {
+        let close_to_current = self
+            .destination
+            .is_some_and(|current| current.distance(destination) < REPATH_DISTANCE);
+        // Having been pushed off a path that is used up, the agent needs a new one.
+        let stranded = self.path.is_empty() && !Self::within_reach(destination, position);
+        if close_to_current && !stranded {
+            return;
+        }
+
+        self.destination = Some(destination);
+        self.path = match grid {
+            Some(grid) => grid.find_path(position, destination),
+            None => vec![destination],
+        };
+    }
+
+    pub fn stop(&mut self) {
+        self.destination = None;
+        self.path.clear();
+    }
+
+    /// The agent has nowhere left to go.
+    pub fn arrived(&self) -> bool {
+        self.path.is_empty()
+    }
+
+    /// The agent has nowhere left to go and stands at `destination`, rather than having
+    /// given up on it because there was no path.
+    pub fn reached(&self, destination: Vec3, position: Vec3) -> bool {
+        self.arrived() && Self::within_reach(destination, position)
+    }
+
+    fn within_reach(destination: Vec3, position: Vec3) -> bool {
+        destination.xz().distance(position.xz()) <= ARRIVAL_RADIUS
+    }
+
+    /// Flat direction from `position` to the next waypoint, dropping the waypoints already reached.
+    pub fn steer(&mut self, position: Vec3) -> Vec3 {
+        while let Some(waypoint) = self.path.first() {
+            let offset = (*waypoint - position).with_y(0.0);
+            if offset.length() > WAYPOINT_RADIUS {
+                return offset.normalize();
+            }
+            self.path.remove(0);
+        }
+        Vec3::ZERO
+    }
+}
+
+/// Builds the `NavGrid` from the fixed colliders once they made it into the physics world.
+///
+/// A cell is walkable when there is ground under its center and an agent standing on it
+/// wouldn't overlap anything.
+/// Only the fixed colliders of the level, not the combatants or their projectiles.
+/// Shared by everything that asks what the level looks like, so they agree on it.
+pub fn level_filter() -> QueryFilter<'static> {
+    QueryFilter::only_fixed().groups(CollisionGroups::new(
+        Group::ALL,
+        Group::ALL - Team::Player.group() - Team::Enemy.group() - PROJECTILE_GROUP,
+    ))
+}
+
+pub fn build_nav_grid(
+    mut commands: Commands,
+    settings: Res<NavGridSettings>,
+    rapier_context: ReadRapierContext,
+) {
+    let Ok(context) = rapier_context.single() else {
+        return;
+    };
+
+    let filter = level_filter();
+    // Starting below the top of taller obstacles makes the ray hit them at once,
+    // so the agent probe on top of that ground overlaps them and the cell is blocked.
+    let ground_below = |position: Vec3| {
+        let from = position.with_y(settings.max_step);
+        context
+            .cast_ray(from, Vec3::NEG_Y, settings.max_step + 0.5, true, filter)
+            .map(|(_, distance)| from.y - distance)
+    };
+
+    let bounds = settings.bounds;
+    // The colliders only show up in queries after the first physics step.
+    if ground_below(bounds.center().extend(0.0).xzy()).is_none() {
+        return;
+    }
+
+    let size = (bounds.size() / settings.cell_size).ceil().as_ivec2();
+    let mut grid = NavGrid {
+        origin: bounds.min,
+        cell_size: settings.cell_size,
+        width: size.x,
+        depth: size.y,
+        walkable: vec![false; (size.x * size.y) as usize],
+    };
+
+    // Shrunk a little so resting on the ground or brushing past a wall doesn't count as blocked.
+    let half_height = settings.agent_height / 2.0;
+    let agent = Collider::cuboid(
+        settings.agent_radius - 0.05,
+        half_height - 0.05,
+        settings.agent_radius - 0.05,
+    );
+    for z in 0..grid.depth {
+        for x in 0..grid.width {
+            let cell = IVec2::new(x, z);
+            let Some(ground) = ground_below(grid.center(cell)) else {
+                continue;
+            };
+
+            let mut blocked = false;
+            context.intersections_with_shape(
+                grid.center(cell).with_y(ground + half_height + 0.05),
+                Quat::IDENTITY,
+                &agent,
+                filter,
+                |_| {
+                    blocked = true;
+                    false
+                },
+            );
+            let index = grid.index(cell);
+            grid.walkable[index] = !blocked;
+        }
+    }
+
+    info!(
+        "Built navigation grid with {} of {} cells walkable",
+        grid.walkable.iter().filter(|walkable| **walkable).count(),
+        grid.walkable.len()
+    );
+    commands.insert_resource(grid);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A 10 by 10 grid of one meter cells with a wall across `x = 5`, open at the cells in `gaps`.
+    fn walled_grid(gaps: &[i32]) -> NavGrid {
+        let (width, depth) = (10, 10);
+        let mut walkable = vec![true; (width * depth) as usize];
+        for z in 0..depth {
+            if !gaps.contains(&z) {
+                walkable[(z * width + 5) as usize] = false;
+            }
+        }
+        NavGrid {
+            origin: Vec2::ZERO,
+            cell_size: 1.0,
+            width,
+            depth,
+            walkable,
+        }
+    }
+
+    #[test]
+    fn finds_a_path_through_the_gap() {
+        let grid = walled_grid(&[9]);
+        let from = Vec3::new(1.5, 0.0, 1.5);
+        let to = Vec3::new(8.5, 0.0, 1.5);
+
+        let path = grid.find_path(from, to);
+
+        assert_eq!(path.last(), Some(&to));
+        assert!(path.iter().all(|&waypoint| grid.is_walkable(waypoint)));
+        assert!(path.contains(&Vec3::new(5.5, 0.0, 9.5)));
+    }
+
+    #[test]
+    fn ends_paths_leaving_the_grid_on_its_edge() {
+        let grid = walled_grid(&[9]);
+        let outside = Vec3::new(1.5, 0.0, -20.0);
+
+        assert!(!grid.is_walkable(outside));
+        assert_eq!(
+            grid.nearest_walkable(outside),
+            Some(Vec3::new(1.5, 0.0, 0.5))
+        );
+        let path = grid.find_path(Vec3::new(1.5, 0.0, 5.5), outside);
+        assert_eq!(path.last(), Some(&Vec3::new(1.5, 0.0, 0.5)));
+    }
+
+    #[test]
+    fn agents_tell_arriving_from_giving_up() {
+        let grid = walled_grid(&[]);
+        let start = Vec3::new(1.5, 0.0, 1.5);
+        let mut agent = NavAgent::default();
+
+        agent.go_to(Vec3::new(8.5, 0.0, 1.5), start, Some(&grid));
+        assert!(agent.arrived());
+        assert!(!agent.reached(Vec3::new(8.5, 0.0, 1.5), start));
+
+        let destination = Vec3::new(3.5, 0.0, 1.5);
+        agent.go_to(destination, start, Some(&grid));
+        assert!(!agent.arrived());
+        // Walk the path, one waypoint after the other.
+        while let Some(&waypoint) = agent.path.first() {
+            agent.steer(waypoint);
+        }
+        assert!(agent.reached(destination, destination));
+
+        // Pushed away after arriving, the agent plans its way back.
+        let pushed = Vec3::new(1.5, 0.0, 4.5);
+        agent.go_to(destination + Vec3::X * 0.2, pushed, Some(&grid));
+        assert!(!agent.arrived());
+    }
+
+    #[test]
+    fn finds_no_path_through_a_closed_wall() {
+        let grid = walled_grid(&[]);
+
+        let path = grid.find_path(Vec3::new(1.5, 0.0, 1.5), Vec3::new(8.5, 0.0, 1.5));
+
+        assert!(path.is_empty());
+    }
+}
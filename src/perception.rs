@@ -0,0 +1,110 @@
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+use serde::Deserialize;
+
+use crate::enemy::{Dying, Enemy};
+use crate::entity::{Health, Player, PlayerHead};
+use crate::weapon::GunshotEvent;
+
+/// Senses an enemy notices the player with.
+#[derive(Debug, Clone, Component, Deserialize)]
+#[serde(default)]
+pub struct Perception {
+    /// How far the enemy can see, in meters.
+    pub sight_range: f32,
+    /// Half-angle of the view cone around the direction the enemy faces, in radians.
+    pub view_angle: f32,
+    /// Player shots fired closer than this are heard even without line of sight, in meters.
+    pub hearing_radius: f32,
+    /// Seconds the last known position of the player is remembered after losing track of them.
+    pub memory: f32,
+    /// Height of the eyes above the origin of the enemy.
+    pub eye_height: f32,
+}
+
+impl Default for Perception {
+    fn default() -> Self {
+        Self {
+            sight_range: 25.0,
+            view_angle: 60.0_f32.to_radians(),
+            hearing_radius: 20.0,
+            memory: 8.0,
+            eye_height: 0.4,
+        }
+    }
+}
+
+/// What an enemy currently knows about the player, refreshed by `perceive_player`.
+#[derive(Debug, Default, Clone, Component)]
+pub struct Awareness {
+    /// The player is in sight right now.
+    pub sees_player: bool,
+    /// Where the player was last seen or heard, or `None` once it is forgotten.
+    pub last_known_position: Option<Vec3>,
+    /// Seconds since the last known position was updated.
+    pub since_last_known: f32,
+}
+
+/// Lets enemies see the player inside their view cone when no wall is in the way,
+/// hear the player's shots, and remember where the player was for a while.
+pub fn perceive_player(
+    time: Res<Time>,
+    rapier_context: ReadRapierContext,
+    mut gunshots: EventReader<GunshotEvent>,
+    player: Single<(Entity, &Transform, &Health), With<Player>>,
+    head: Single<&GlobalTransform, With<PlayerHead>>,
+    mut enemies: Query<
+        (Entity, &Transform, &Perception, &mut Awareness),
+        (With<Enemy>, Without<Dying>, Without<Player>),
+    >,
+) {
+    let Ok(context) = rapier_context.single() else {
+        return;
+    };
+    let (player, player_transform, health) = player.into_inner();
+    let player_eyes = head.translation();
+    let shots: Vec<Vec3> = gunshots
+        .read()
+        .filter(|shot| shot.shooter == player)
+        .map(|shot| shot.origin)
+        .collect();
+
+    for (entity, transform, perception, mut awareness) in &mut enemies {
+        let eyes = transform.translation + Vec3::Y * perception.eye_height;
+        let to_player = player_eyes - eyes;
+        let distance = to_player.length();
+
+        let in_view_cone = distance <= perception.sight_range
+            && transform.forward().angle_between(to_player) <= perception.view_angle;
+        // The dead don't draw any attention.
+        awareness.sees_player = !health.is_dead()
+            && in_view_cone
+            && context
+                .cast_ray(
+                    eyes,
+                    to_player / distance,
+                    distance,
+                    true,
+                    // Projectiles flying by don't block the view.
+                    QueryFilter::exclude_dynamic().exclude_collider(entity),
+                )
+                .is_some_and(|(hit, _)| hit == player);
+
+        let heard = shots
+            .iter()
+            .rfind(|origin| origin.distance(transform.translation) <= perception.hearing_radius);
+
+        if awareness.sees_player {
+            awareness.last_known_position = Some(player_transform.translation);
+            awareness.since_last_known = 0.0;
+        } else if let Some(origin) = heard {
+            awareness.last_known_position = Some(*origin);
+            awareness.since_last_known = 0.0;
+        } else {
+            awareness.since_last_known += time.delta_secs();
+            if awareness.since_last_known > perception.memory {
+                awareness.last_known_position = None;
+            }
+        }
+    }
+}
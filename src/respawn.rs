@@ -0,0 +1,229 @@
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+
+use crate::ball::Ball;
+use crate::damage::{DamageDealtEvent, DeathEvent, ReviveEvent};
+use crate::enemy::{Dying, Enemy};
+use crate::entity::{Player, PlayerHead};
+use crate::game_state::GameState;
+use crate::movement::{
+    JumpState, MovementSettings, MovementState, PlayerVelocity, player_collider,
+};
+use crate::sights::AimDownSights;
+use crate::view_model::EYE_OFFSET;
+use crate::weapon::{Holster, Weapon};
+
+/// Where and how quickly the player comes back after dying.
+#[derive(Debug, Clone, Resource)]
+pub struct RespawnSettings {
+    /// Seconds between dying and respawning.
+    pub delay: f32,
+    /// Points on the floor the player can respawn at. The one farthest from any enemy is picked.
+    pub spawn_points: Vec<Vec3>,
+}
+
+impl Default for RespawnSettings {
+    fn default() -> Self {
+        Self {
+            delay: 3.0,
+            spawn_points: vec![
+                Vec3::ZERO,
+                Vec3::new(-8.0, 0.0, 8.0),
+                Vec3::new(8.0, 0.0, -8.0),
+            ],
+        }
+    }
+}
+
+impl RespawnSettings {
+    /// The spawn point farthest from the closest of `enemies`.
+    fn pick_spawn_point(&self, enemies: &[Vec3]) -> Vec3 {
+        let distance_to_enemies = |point: Vec3| {
+            enemies
+                .iter()
+                .map(|enemy| enemy.distance_squared(point))
+                .fold(f32::INFINITY, f32::min)
+        };
+
+        self.spawn_points
+            .iter()
+            .copied()
+            .reduce(|best, point| {
+                if distance_to_enemies(point) > distance_to_enemies(best) {
+                    point
+                } else {
+                    best
+                }
+            })
+            .unwrap_or(Vec3::ZERO)
+    }
+}
+
+/// What the player achieved during the current life, shown on the game over overlay.
+#[derive(Debug, Default, Clone, Resource)]
+pub struct PlayerStats {
+    pub kills: u32,
+    pub damage_dealt: i32,
+    /// Virtual time the player last spawned at, in seconds.
+    pub spawned_at: f32,
+}
+
+/// Counts down to the respawn while the player is dead.
+#[derive(Resource)]
+pub struct RespawnTimer(pub Timer);
+
+/// Tag for the text counting down to the respawn.
+#[derive(Component)]
+pub struct RespawnCountdown;
+
+/// Credits the player with the damage they deal and the enemies they kill.
+pub fn track_stats(
+    mut dealt_events: EventReader<DamageDealtEvent>,
+    mut death_events: EventReader<DeathEvent>,
+    player: Single<Entity, With<Player>>,
+    mut stats: ResMut<PlayerStats>,
+) {
+    let player = *player;
+    for damage in dealt_events.read() {
+        if damage.source == Some(player) {
+            stats.damage_dealt += damage.amount;
+        }
+    }
+    for death in death_events.read() {
+        if death.killer == Some(player) && death.entity != player {
+            stats.kills += 1;
+        }
+    }
+}
+
+/// Switches to the dead state once the player runs out of health.
+pub fn kill_player(
+    mut death_events: EventReader<DeathEvent>,
+    player: Single<Entity, With<Player>>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    for death in death_events.read() {
+        if death.entity == *player {
+            info!("Player died!");
+            next_state.set(GameState::Dead);
+        }
+    }
+}
+
+/// Starts the respawn countdown and shows the game over overlay with the stats of the last life.
+pub fn show_game_over(
+    mut commands: Commands,
+    time: Res<Time>,
+    settings: Res<RespawnSettings>,
+    stats: Res<PlayerStats>,
+) {
+    commands.insert_resource(RespawnTimer(Timer::from_seconds(
+        settings.delay,
+        TimerMode::Once,
+    )));
+
+    let survived = time.elapsed_secs() - stats.spawned_at;
+    commands
+        .spawn((
+            StateScoped(GameState::Dead),
+            Node {
+                position_type: PositionType::Absolute,
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                flex_direction: FlexDirection::Column,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            BackgroundColor(Color::srgba(0.5, 0.0, 0.0, 0.5)),
+        ))
+        .with_children(|overlay| {
+            overlay.spawn(Text::new(format!(
+                "GAME OVER\n\nKills: {}\nDamage dealt: {}\nSurvived: {survived:.1} s\n",
+                stats.kills, stats.damage_dealt
+            )));
+            overlay.spawn((RespawnCountdown, Text::default()));
+        });
+}
+
+/// Counts down to the respawn, then puts the player back at full health and ammo on a spawn
+/// point and clears the projectiles still flying around.
+pub fn respawn_player(
+    mut commands: Commands,
+    time: Res<Time>,
+    settings: Res<RespawnSettings>,
+    movement_settings: Res<MovementSettings>,
+    mut timer: ResMut<RespawnTimer>,
+    mut stats: ResMut<PlayerStats>,
+    mut next_state: ResMut<NextState<GameState>>,
+    mut revive_events: EventWriter<ReviveEvent>,
+    mut countdown: Query<&mut Text, With<RespawnCountdown>>,
+    balls: Query<Entity, With<Ball>>,
+    enemies: Query<&GlobalTransform, (With<Enemy>, Without<Dying>)>,
+    player: Single<
+        (
+            Entity,
+            &mut Transform,
+            &mut Collider,
+            &mut KinematicCharacterController,
+            &mut MovementState,
+            &mut PlayerVelocity,
+            &mut JumpState,
+            &mut Weapon,
+            &mut Holster,
+            &mut AimDownSights,
+        ),
+        With<Player>,
+    >,
+    head: Single<&mut Transform, (With<PlayerHead>, Without<Player>)>,
+) {
+    timer.0.tick(time.delta());
+    for mut text in &mut countdown {
+        text.0 = format!("Respawning in {:.0}", timer.0.remaining_secs().ceil());
+    }
+    if !timer.0.finished() {
+        return;
+    }
+
+    for ball in &balls {
+        commands.entity(ball).despawn();
+    }
+
+    let (
+        entity,
+        mut transform,
+        mut collider,
+        mut controller,
+        mut state,
+        mut velocity,
+        mut jump_state,
+        mut weapon,
+        mut holster,
+        mut sights,
+    ) = player.into_inner();
+    let enemy_positions: Vec<Vec3> = enemies.iter().map(|enemy| enemy.translation()).collect();
+    let half_height = movement_settings.walk.half_height;
+
+    // Stand up on the spawn point, looking straight ahead.
+    *transform = Transform::from_translation(
+        settings.pick_spawn_point(&enemy_positions) + Vec3::Y * half_height,
+    );
+    *head.into_inner() = Transform::from_xyz(0.0, half_height - EYE_OFFSET, 0.0);
+    *collider = player_collider(half_height);
+    controller.translation = None;
+    *state = MovementState::default();
+    *velocity = PlayerVelocity::default();
+    *jump_state = JumpState::default();
+    weapon.refill();
+    for weapon in &mut holster.0 {
+        weapon.refill();
+    }
+    sights.amount = 0.0;
+
+    revive_events.write(ReviveEvent { target: entity });
+    *stats = PlayerStats {
+        spawned_at: time.elapsed_secs(),
+        ..default()
+    };
+    next_state.set(GameState::Playing);
+}
@@ -1,17 +1,24 @@
-use bevy::prelude::{Component, Deref, DerefMut, Vec2};
+use bevy::prelude::{Component, Vec2};
 
-#[derive(Debug, Component, Deref, DerefMut)]
-pub struct CameraSensitivity(Vec2);
+#[derive(Debug, Clone, Copy, Component)]
+pub struct CameraSensitivity {
+    /// Radians turned per pixel of mouse movement, for yaw and pitch.
+    pub mouse: Vec2,
+    /// Radians turned per second with the right stick fully deflected, for yaw and pitch.
+    pub gamepad: Vec2,
+}
 
 impl Default for CameraSensitivity {
     fn default() -> Self {
-        Self(
+        Self {
             // These factors are just arbitrary mouse sensitivity values.
             // It's often nicer to have a faster horizontal sensitivity than vertical.
             // We use a component for them so that we can make them user-configurable at runtime
             // for accessibility reasons.
             // It also allows you to inspect them in an editor if you `Reflect` the component.
-            Vec2::new(0.01, 0.002),
-        )
+            mouse: Vec2::new(0.01, 0.002),
+            // A stick reports a rate rather than a distance, so these are in radians per second.
+            gamepad: Vec2::new(4.0, 2.5),
+        }
     }
 }
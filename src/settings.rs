@@ -0,0 +1,190 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+
+use bevy::audio::{GlobalVolume, Volume};
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::entity::Player;
+use crate::input_map::{Action, Binding, InputMap};
+use crate::sensitivity::CameraSensitivity;
+use crate::sights::AimDownSights;
+use crate::world_model::{MAX_WORLD_FOV, MIN_WORLD_FOV};
+
+/// Name of the directory inside the user config directory that holds our files.
+const CONFIG_DIR_NAME: &str = "dyson";
+
+const SETTINGS_FILE_NAME: &str = "settings.ron";
+
+/// Settings the player can change, saved to `settings.ron` in the user config directory on exit
+/// and loaded again on startup.
+///
+/// Fields missing from the file keep their defaults, so adding a setting doesn't invalidate
+/// files written by older builds.
+#[derive(Debug, Clone, Resource, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UserSettings {
+    /// Mouse sensitivity for yaw and pitch, see `CameraSensitivity`.
+    pub sensitivity: Vec2,
+    /// Right stick sensitivity for yaw and pitch, see `CameraSensitivity`.
+    pub gamepad_sensitivity: Vec2,
+    /// FOV of the world model, in degrees.
+    pub world_fov: f32,
+    /// Master volume, where 1.0 plays sounds at their original level.
+    pub volume: f32,
+    pub bindings: BTreeMap<Action, Vec<Binding>>,
+}
+
+impl Default for UserSettings {
+    fn default() -> Self {
+        Self {
+            sensitivity: CameraSensitivity::default().mouse,
+            gamepad_sensitivity: CameraSensitivity::default().gamepad,
+            world_fov: 90.0,
+            volume: 1.0,
+            bindings: InputMap::default()
+                .iter()
+                .map(|(action, bindings)| (action, bindings.to_vec()))
+                .collect(),
+        }
+    }
+}
+
+impl UserSettings {
+    /// Replaces values a hand-edited file could have broken with something usable.
+    fn validate(&mut self) {
+        let defaults = Self::default();
+
+        if !self.sensitivity.is_finite() || self.sensitivity.cmple(Vec2::ZERO).any() {
+            warn!(
+                "Invalid sensitivity {} in settings, using {}",
+                self.sensitivity, defaults.sensitivity
+            );
+            self.sensitivity = defaults.sensitivity;
+        }
+
+        if !self.gamepad_sensitivity.is_finite() || self.gamepad_sensitivity.cmple(Vec2::ZERO).any()
+        {
+            warn!(
+                "Invalid gamepad sensitivity {} in settings, using {}",
+                self.gamepad_sensitivity, defaults.gamepad_sensitivity
+            );
+            self.gamepad_sensitivity = defaults.gamepad_sensitivity;
+        }
+
+        if !self.world_fov.is_finite() {
+            warn!(
+                "Invalid world FOV in settings, using {}",
+                defaults.world_fov
+            );
+            self.world_fov = defaults.world_fov;
+        }
+        self.world_fov = self.world_fov.clamp(MIN_WORLD_FOV, MAX_WORLD_FOV);
+
+        if !self.volume.is_finite() {
+            warn!("Invalid volume in settings, using {}", defaults.volume);
+            self.volume = defaults.volume;
+        }
+        self.volume = self.volume.clamp(0.0, 1.0);
+
+        for bindings in self.bindings.values_mut() {
+            let mut seen = Vec::with_capacity(bindings.len());
+            bindings.retain(|binding| {
+                let duplicate = seen.contains(binding);
+                seen.push(*binding);
+                !duplicate
+            });
+        }
+    }
+}
+
+fn settings_path() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join(CONFIG_DIR_NAME).join(SETTINGS_FILE_NAME))
+}
+
+/// Reads the settings file and applies it, falling back to the defaults if it is missing or corrupt.
+pub fn load_settings(
+    mut settings: ResMut<UserSettings>,
+    mut input_map: ResMut<InputMap>,
+    mut global_volume: ResMut<GlobalVolume>,
+) {
+    let Some(path) = settings_path() else {
+        warn!("No user config directory found, settings will not be persisted");
+        return;
+    };
+
+    match fs::read_to_string(&path) {
+        Ok(contents) => match ron::from_str::<UserSettings>(&contents) {
+            Ok(loaded) => *settings = loaded,
+            Err(error) => warn!(
+                "Could not parse {}, using default settings: {error}",
+                path.display()
+            ),
+        },
+        // The first run has nothing to load yet.
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => {}
+        Err(error) => warn!(
+            "Could not read {}, using default settings: {error}",
+            path.display()
+        ),
+    }
+
+    settings.validate();
+
+    // Actions the file doesn't mention keep their default bindings.
+    for (action, bindings) in &settings.bindings {
+        input_map.rebind(*action, bindings.iter().copied());
+    }
+    global_volume.volume = Volume::Linear(settings.volume);
+}
+
+/// Collects the current settings from the world and writes them to the settings file on exit.
+pub fn save_settings(
+    mut exit_events: EventReader<AppExit>,
+    mut settings: ResMut<UserSettings>,
+    input_map: Res<InputMap>,
+    global_volume: Res<GlobalVolume>,
+    sights: Query<&AimDownSights, With<Player>>,
+) {
+    if exit_events.read().last().is_none() {
+        return;
+    }
+
+    // Save what the player picked, not the zoom of the sights they might be looking down.
+    if let Ok(sights) = sights.single() {
+        settings.sensitivity = sights.sensitivity.mouse;
+        settings.gamepad_sensitivity = sights.sensitivity.gamepad;
+        settings.world_fov = sights.fov.to_degrees();
+    }
+    settings.volume = global_volume.volume.to_linear();
+    settings.bindings = input_map
+        .iter()
+        .map(|(action, bindings)| (action, bindings.to_vec()))
+        .collect();
+
+    let Some(path) = settings_path() else {
+        return;
+    };
+
+    let pretty = ron::ser::PrettyConfig::default();
+    let contents = match ron::ser::to_string_pretty(&*settings, pretty) {
+        Ok(contents) => contents,
+        Err(error) => {
+            error!("Could not serialize settings: {error}");
+            return;
+        }
+    };
+
+    if let Some(dir) = path.parent()
+        && let Err(error) = fs::create_dir_all(dir)
+    {
+        error!("Could not create {}: {error}", dir.display());
+        return;
+    }
+
+    match fs::write(&path, contents) {
+        Ok(()) => info!("Saved settings to {}", path.display()),
+        Err(error) => error!("Could not write {}: {error}", path.display()),
+    }
+}
@@ -0,0 +1,67 @@
+use bevy::prelude::*;
+
+use crate::entity::Player;
+use crate::game_state::GameState;
+use crate::input_map::Action;
+use crate::sensitivity::CameraSensitivity;
+use crate::view_model::{ARM_AIMED_POSITION, ARM_HIP_POSITION, ViewModelArm};
+use crate::weapon::Weapon;
+use crate::world_model::WorldModelCamera;
+
+/// How quickly the sights follow the alt-fire button.
+const SIGHTS_SMOOTHING: f32 = 15.0;
+
+/// How far the player has raised the sights of their weapon, and the view they zoom in from.
+#[derive(Debug, Component)]
+pub struct AimDownSights {
+    /// 0 when firing from the hip, 1 when fully aimed.
+    pub amount: f32,
+    /// FOV of the world model the player picked, in radians. The camera gets a narrower one
+    /// while aiming.
+    pub fov: f32,
+    /// Camera sensitivity the player picked. The camera turns slower while aiming.
+    pub sensitivity: CameraSensitivity,
+}
+
+impl AimDownSights {
+    /// Sights lowered, zooming in from `fov` and `sensitivity`.
+    pub fn new(fov: f32, sensitivity: CameraSensitivity) -> Self {
+        Self {
+            amount: 0.0,
+            fov,
+            sensitivity,
+        }
+    }
+}
+
+/// Raises the sights while alt-fire is held: narrows the FOV of the world model, slows the
+/// camera down by as much, and brings the arm to the middle of the view.
+pub fn aim_down_sights(
+    time: Res<Time>,
+    state: Res<State<GameState>>,
+    actions: Res<ButtonInput<Action>>,
+    player: Single<(&Weapon, &mut AimDownSights, &mut CameraSensitivity), With<Player>>,
+    mut world_model_projection: Single<&mut Projection, With<WorldModelCamera>>,
+    mut arm: Single<&mut Transform, With<ViewModelArm>>,
+) {
+    let Projection::Perspective(perspective) = world_model_projection.as_mut() else {
+        unreachable!(
+            "The `Projection` component was explicitly built with `Projection::Perspective`"
+        );
+    };
+    let (weapon, mut sights, mut sensitivity) = player.into_inner();
+
+    // Dying lowers the sights. While paused, time stands still and so do they.
+    let aiming = *state.get() == GameState::Playing && actions.pressed(Action::AltFire);
+    let target = if aiming { 1.0 } else { 0.0 };
+    // Exponential decay keeps the easing independent of the frame rate.
+    let blend = 1.0 - (-SIGHTS_SMOOTHING * time.delta_secs()).exp();
+    sights.amount = sights.amount.lerp(target, blend);
+
+    let zoom = 1.0.lerp(weapon.ads_zoom, sights.amount);
+    perspective.fov = sights.fov * zoom;
+    sensitivity.mouse = sights.sensitivity.mouse * zoom;
+    sensitivity.gamepad = sights.sensitivity.gamepad * zoom;
+
+    arm.translation = ARM_HIP_POSITION.lerp(ARM_AIMED_POSITION, sights.amount);
+}
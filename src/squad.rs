@@ -0,0 +1,212 @@
+use bevy::platform::collections::HashMap;
+use bevy::prelude::*;
+
+use crate::enemy::{Dying, Enemy};
+use crate::entity::Health;
+use crate::perception::Awareness;
+
+/// How the enemies fighting the player share the work between them.
+#[derive(Debug, Clone, Resource)]
+pub struct SquadSettings {
+    /// Share of the healthy, mobile enemies in a fight that go around the player's side.
+    /// At least one of them always stays back to keep the player busy.
+    pub flank_share: f32,
+    /// Distance from the player flankers take up position at, as a fraction of their attack range.
+    pub flank_range: f32,
+    /// Fraction of its health below which an enemy backs off while still shooting.
+    pub retreat_below: f32,
+    /// Distance from the player retreating enemies back off to, as a fraction of their attack range.
+    pub retreat_range: f32,
+    /// Least seconds between two shots of the squad, so they don't all fire at once.
+    pub shot_spacing: f32,
+    /// Enemies closer than this push each other apart, in meters.
+    pub separation_radius: f32,
+    /// Speed enemies on top of each other push apart with, in meters per second.
+    pub separation_speed: f32,
+}
+
+impl Default for SquadSettings {
+    fn default() -> Self {
+        Self {
+            flank_share: 0.5,
+            flank_range: 0.7,
+            retreat_below: 0.5,
+            retreat_range: 0.9,
+            shot_spacing: 0.3,
+            separation_radius: 2.0,
+            separation_speed: 2.0,
+        }
+    }
+}
+
+/// Part an enemy plays in the fight against the player.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SquadRole {
+    /// Closes in and keeps shooting from the front.
+    Suppress,
+    /// Goes around the player's side and shoots from there. `side` is 1 for the right
+    /// of the squad's front and -1 for the left.
+    Flank { side: f32 },
+    /// Backs off to the edge of its attack range while shooting.
+    Retreat,
+}
+
+/// Blackboard the enemies share, kept up to date by `update_squad`.
+#[derive(Debug, Default, Resource)]
+pub struct Squad {
+    /// Role of every enemy that knows about the player.
+    pub roles: HashMap<Entity, SquadRole>,
+    /// Freshest position of the player any member knows of.
+    pub player_position: Option<Vec3>,
+    /// Flat direction from the player towards the middle of the enemies fighting them.
+    pub front: Vec3,
+    /// Where every living enemy stands.
+    pub positions: Vec<(Entity, Vec3)>,
+    /// Elapsed seconds before which no member may fire again.
+    pub next_shot: f32,
+}
+
+impl Squad {
+    pub fn role(&self, entity: Entity) -> Option<SquadRole> {
+        self.roles.get(&entity).copied()
+    }
+
+    /// Where the role of `entity` wants it to stand, or `None` if it should fight as usual.
+    pub fn post(
+        &self,
+        entity: Entity,
+        position: Vec3,
+        attack_range: f32,
+        settings: &SquadSettings,
+    ) -> Option<Vec3> {
+        let player = self.player_position?;
+        match self.role(entity)? {
+            SquadRole::Suppress => None,
+            SquadRole::Flank { side } => {
+                let across = Vec3::new(-self.front.z, 0.0, self.front.x) * side;
+                Some(player + across * attack_range * settings.flank_range)
+            }
+            SquadRole::Retreat => {
+                let distance = attack_range * settings.retreat_range;
+                let away = (position - player).with_y(0.0);
+                (away.length() < distance).then(|| player + away.normalize_or_zero() * distance)
+            }
+        }
+    }
+
+    /// Claims the next shot of the squad at `now`, unless a member fired too recently.
+    pub fn take_shot(&mut self, now: f32, spacing: f32) -> bool {
+        if now < self.next_shot {
+            return false;
+        }
+        self.next_shot = now + spacing;
+        true
+    }
+
+    /// Flat velocity pushing `entity` away from the enemies crowding it.
+    pub fn separation(&self, entity: Entity, position: Vec3, settings: &SquadSettings) -> Vec3 {
+        let radius = settings.separation_radius;
+        let push: Vec3 = self
+            .positions
+            .iter()
+            .filter(|(other, _)| *other != entity)
+            .map(|(_, other)| {
+                let offset = (position - *other).with_y(0.0);
+                let distance = offset.length();
+                if distance >= radius || distance == 0.0 {
+                    return Vec3::ZERO;
+                }
+                // Pushes harder the closer they are.
+                offset / distance * (1.0 - distance / radius)
+            })
+            .sum();
+        push * settings.separation_speed
+    }
+}
+
+/// Refreshes the blackboard and hands out roles to the enemies that know about the player.
+///
+/// Enemies keep their role while they can, so they don't change their minds every frame.
+pub fn update_squad(
+    settings: Res<SquadSettings>,
+    mut squad: ResMut<Squad>,
+    enemies: Query<(Entity, &Transform, &Enemy, &Awareness, &Health), Without<Dying>>,
+) {
+    squad.positions = enemies
+        .iter()
+        .map(|(entity, transform, ..)| (entity, transform.translation))
+        .collect();
+
+    let mut engaged: Vec<_> = enemies
+        .iter()
+        .filter(|(.., awareness, _)| awareness.last_known_position.is_some())
+        .collect();
+    engaged.sort_by_key(|(entity, ..)| *entity);
+
+    squad.player_position = engaged
+        .iter()
+        .min_by(|(.., a, _), (.., b, _)| a.since_last_known.total_cmp(&b.since_last_known))
+        .and_then(|(.., awareness, _)| awareness.last_known_position);
+    let Some(player) = squad.player_position else {
+        squad.roles.clear();
+        return;
+    };
+
+    let middle = engaged
+        .iter()
+        .map(|(_, transform, ..)| transform.translation)
+        .sum::<Vec3>()
+        / engaged.len() as f32;
+    squad.front = (middle - player)
+        .with_y(0.0)
+        .try_normalize()
+        .unwrap_or(Vec3::X);
+
+    let mut roles = HashMap::default();
+    let mut fighters = Vec::new();
+    for (entity, _, enemy, _, health) in &engaged {
+        let role = if enemy.speed <= 0.0 {
+            // Turrets can't go anywhere, all they can do is shoot.
+            SquadRole::Suppress
+        } else if health.fraction() < settings.retreat_below {
+            SquadRole::Retreat
+        } else {
+            fighters.push(*entity);
+            match squad.role(*entity) {
+                Some(SquadRole::Flank { side }) => SquadRole::Flank { side },
+                _ => SquadRole::Suppress,
+            }
+        };
+        roles.insert(*entity, role);
+    }
+
+    let wanted = match fighters.len() {
+        0 | 1 => 0,
+        count => ((count as f32 * settings.flank_share).round() as usize).clamp(1, count - 1),
+    };
+    let is_flanking = |role: Option<&SquadRole>| matches!(role, Some(SquadRole::Flank { .. }));
+    let mut flanking = fighters
+        .iter()
+        .filter(|entity| is_flanking(roles.get(*entity)))
+        .count();
+    for entity in &fighters {
+        let role = roles.get_mut(entity).expect("every fighter has a role");
+        let flanks = matches!(role, SquadRole::Flank { .. });
+        if flanks && flanking > wanted {
+            *role = SquadRole::Suppress;
+            flanking -= 1;
+        } else if !flanks && flanking < wanted {
+            // Alternate the sides, so the player gets caught in between.
+            let side = if flanking % 2 == 0 { 1.0 } else { -1.0 };
+            *role = SquadRole::Flank { side };
+            flanking += 1;
+        }
+    }
+
+    for (entity, role) in &roles {
+        if squad.role(*entity) != Some(*role) {
+            debug!("Enemy {entity} takes the {role:?} role");
+        }
+    }
+    squad.roles = roles;
+}
@@ -3,16 +3,38 @@ use bevy::{
 };
 use bevy_rapier3d::prelude::*;
 
-use crate::entity::{Health, HealthBarFill, Player};
+use crate::damage::PROJECTILE_GROUP;
+use crate::entity::{Health, HealthBarFill, Player, PlayerHead, Team};
+use crate::movement::{
+    JumpState, MovementSettings, MovementState, PlayerVelocity, player_collider,
+};
 use crate::render_layer::VIEW_MODEL_RENDER_LAYER;
 use crate::sensitivity::CameraSensitivity;
+use crate::settings::UserSettings;
+use crate::sights::AimDownSights;
+use crate::weapon::{Holster, Weapon};
 use crate::world_model::WorldModelCamera;
 
+/// Distance of the eyes below the top of the player's collider.
+pub const EYE_OFFSET: f32 = 0.4;
+/// Where the arm is held relative to the head when firing from the hip.
+pub const ARM_HIP_POSITION: Vec3 = Vec3::new(0.2, -0.1, -0.25);
+/// Where the arm is held relative to the head when aiming down the sights.
+pub const ARM_AIMED_POSITION: Vec3 = Vec3::new(0.0, -0.08, -0.3);
+
+/// The arm holding the weapon in the view model.
+#[derive(Debug, Component)]
+pub struct ViewModelArm;
+
 pub fn spawn_view_model(
     mut commands: Commands,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<StandardMaterial>>,
+    movement_settings: Res<MovementSettings>,
+    user_settings: Res<UserSettings>,
 ) {
+    let half_height = movement_settings.walk.half_height;
+
     let arm = meshes.add(Cuboid::new(0.1, 0.1, 0.5));
     let arm_material = materials.add(Color::from(tailwind::TEAL_200));
 
@@ -22,79 +44,125 @@ pub fn spawn_view_model(
     let bg_mat = materials.add(Color::BLACK.with_alpha(0.5));
     let fill_mat = materials.add(Color::from(tailwind::GREEN_500));
 
+    let sensitivity = CameraSensitivity {
+        mouse: user_settings.sensitivity,
+        gamepad: user_settings.gamepad_sensitivity,
+    };
+    let world_fov = user_settings.world_fov.to_radians();
+
     commands
         .spawn((
             Player,
-            Health {
-                current: 10,
-                max: 10,
+            Health::new(10),
+            // The player is moved by the character controller, which slides the collider along
+            // the world geometry instead of letting the solver push a dynamic body around.
+            RigidBody::KinematicPositionBased,
+            player_collider(half_height),
+            KinematicCharacterController {
+                offset: CharacterLength::Absolute(0.02),
+                max_slope_climb_angle: 45.0_f32.to_radians(),
+                min_slope_slide_angle: 30.0_f32.to_radians(),
+                autostep: Some(CharacterAutostep {
+                    max_height: CharacterLength::Absolute(0.3),
+                    min_width: CharacterLength::Absolute(0.2),
+                    include_dynamic_bodies: false,
+                }),
+                snap_to_ground: Some(CharacterLength::Absolute(0.3)),
+                // Projectiles, the player's own too, are pushed aside rather than blocking the way.
+                filter_groups: Some(CollisionGroups::new(
+                    Team::Player.group(),
+                    Group::ALL - PROJECTILE_GROUP,
+                )),
+                ..default()
             },
-            RigidBody::Dynamic,
-            Collider::cuboid(0.5, 1.0, 0.5),
+            MovementState::default(),
+            PlayerVelocity::default(),
+            JumpState::default(),
+            // The ball launcher in hand, with a rifle to switch to and sights to aim down.
+            (
+                Weapon::default(),
+                Holster(vec![Weapon::rifle()]),
+                AimDownSights::new(world_fov, sensitivity),
+            ),
+            Team::Player,
+            CollisionGroups::new(Team::Player.group(), Group::ALL),
             ActiveEvents::COLLISION_EVENTS,
-            Transform::from_xyz(0.0, 1.0, 0.0),
-            CameraSensitivity::default(),
+            // Stand on the floor.
+            Transform::from_xyz(0.0, half_height, 0.0),
+            sensitivity,
             Visibility::default(),
         ))
-        .with_children(|parent| {
-            // --- WORLD CAMERA ---
-            parent.spawn((
-                WorldModelCamera,
-                Camera3d::default(),
-                Projection::from(PerspectiveProjection {
-                    fov: 90.0_f32.to_radians(),
-                    ..default()
-                }),
-            ));
-
-            // --- VIEW MODEL CAMERA (Overlay) ---
-            parent.spawn((
-                Camera3d::default(),
-                Camera {
-                    order: 1,
-                    ..default()
-                },
-                Projection::from(PerspectiveProjection {
-                    fov: 70.0_f32.to_radians(),
-                    ..default()
-                }),
-                RenderLayers::layer(VIEW_MODEL_RENDER_LAYER),
-            ));
-
-            // --- ARMS ---
-            parent.spawn((
-                Mesh3d(arm),
-                MeshMaterial3d(arm_material),
-                Transform::from_xyz(0.2, -0.1, -0.25),
-                RenderLayers::layer(VIEW_MODEL_RENDER_LAYER),
-                NotShadowCaster,
-            ));
-
-            // --- HEALTH BAR CONTAINER ---
-            // Positioned in the bottom-ish center of the view model camera
-            parent
+        .with_children(|player| {
+            // The head only pitches, so the collider on the player stays upright
+            // while looking up and down.
+            player
                 .spawn((
-                    Mesh3d(bar_bg_mesh),
-                    MeshMaterial3d(bg_mat),
-                    Transform::from_xyz(0.0, -0.2, -0.5),
-                    RenderLayers::layer(VIEW_MODEL_RENDER_LAYER),
+                    PlayerHead,
+                    Transform::from_xyz(0.0, half_height - EYE_OFFSET, 0.0),
+                    Visibility::default(),
                 ))
-                .with_children(|bar| {
-                    // THE ACTUAL FILL
-                    bar.spawn((
-                        HealthBarFill,
-                        Mesh3d(bar_fill_mesh),
-                        MeshMaterial3d(fill_mat),
-                        // Slightly in front of the background to avoid flickering
-                        Transform::from_xyz(0.0, 0.0, 0.01),
+                .with_children(|parent| {
+                    // --- WORLD CAMERA ---
+                    parent.spawn((
+                        WorldModelCamera,
+                        Camera3d::default(),
+                        Projection::from(PerspectiveProjection {
+                            fov: world_fov,
+                            ..default()
+                        }),
+                    ));
+
+                    // --- VIEW MODEL CAMERA (Overlay) ---
+                    parent.spawn((
+                        Camera3d::default(),
+                        Camera {
+                            order: 1,
+                            ..default()
+                        },
+                        Projection::from(PerspectiveProjection {
+                            fov: 70.0_f32.to_radians(),
+                            ..default()
+                        }),
                         RenderLayers::layer(VIEW_MODEL_RENDER_LAYER),
                     ));
+
+                    // --- ARMS ---
+                    parent.spawn((
+                        Mesh3d(arm),
+                        MeshMaterial3d(arm_material),
+                        Transform::from_translation(ARM_HIP_POSITION),
+                        RenderLayers::layer(VIEW_MODEL_RENDER_LAYER),
+                        NotShadowCaster,
+                        ViewModelArm,
+                    ));
+
+                    // --- HEALTH BAR CONTAINER ---
+                    // Positioned in the bottom-ish center of the view model camera
+                    parent
+                        .spawn((
+                            Mesh3d(bar_bg_mesh),
+                            MeshMaterial3d(bg_mat),
+                            Transform::from_xyz(0.0, -0.2, -0.5),
+                            RenderLayers::layer(VIEW_MODEL_RENDER_LAYER),
+                        ))
+                        .with_children(|bar| {
+                            // THE ACTUAL FILL
+                            bar.spawn((
+                                HealthBarFill,
+                                Mesh3d(bar_fill_mesh),
+                                MeshMaterial3d(fill_mat),
+                                // Slightly in front of the background to avoid flickering
+                                Transform::from_xyz(0.0, 0.0, 0.01),
+                                RenderLayers::layer(VIEW_MODEL_RENDER_LAYER),
+                            ));
+                        });
                 });
         });
 }
 
+/// Resizes the health bar whenever the player is damaged, healed or revived.
 pub fn update_health_bar(
-    // 0.16 still uses Changed<T> for efficient updates
+    // Only redraw once `apply_health_events` actually changed the health.
     player_query: Query<&Health, (With<Player>, Changed<Health>)>,
     // REPLACED: Parent -> ChildOf
     mut fill_query: Query<(&mut Transform, &ChildOf), With<HealthBarFill>>,
@@ -103,7 +171,7 @@ pub fn update_health_bar(
 
     // We get the player's current health state
     if let Ok(health) = player_query.single() {
-        let health_pct = (health.current as f32 / health.max as f32).clamp(0.0, 1.0);
+        let health_pct = health.fraction();
 
         for (mut fill_transform, _) in &mut fill_query {
             // child_of_component.get() returns the Entity ID of the parent
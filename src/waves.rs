@@ -0,0 +1,235 @@
+use bevy::asset::LoadedFolder;
+use bevy::pbr::StandardMaterial;
+use bevy::prelude::*;
+use rand::seq::SliceRandom;
+
+use crate::archetype::{EnemyArchetype, EnemyArchetypes};
+use crate::enemy::{Difficulty, Dying, Enemy, spawn_enemy};
+use crate::entity::Player;
+
+/// How the waves are put together and how quickly they get harder.
+#[derive(Debug, Clone, Resource)]
+pub struct WaveSettings {
+    /// Points on the floor enemies spawn at.
+    pub spawn_points: Vec<Vec3>,
+    /// Spawn points closer to the player than this are only used if there is no other, in meters.
+    pub min_spawn_distance: f32,
+    /// Seconds before the first wave.
+    pub first_wave_delay: f32,
+    /// Seconds between clearing a wave and the next one starting.
+    pub break_time: f32,
+    /// Seconds between two enemies of the same wave spawning.
+    pub spawn_interval: f32,
+    pub first_wave_size: u32,
+    /// Enemies every wave brings on top of the previous one.
+    pub extra_enemies_per_wave: u32,
+    /// Health added to the enemies every wave, as a fraction of the health of their archetype.
+    pub health_growth: f32,
+    /// Speed added to the enemies every wave, as a fraction of the speed of their archetype.
+    pub speed_growth: f32,
+    /// Accuracy added to the enemies every wave.
+    pub accuracy_growth: f32,
+}
+
+impl Default for WaveSettings {
+    fn default() -> Self {
+        Self {
+            spawn_points: vec![
+                Vec3::new(5.0, 0.0, 5.0),
+                Vec3::new(-15.0, 0.0, -15.0),
+                Vec3::new(15.0, 0.0, -15.0),
+                Vec3::new(-15.0, 0.0, 15.0),
+                Vec3::new(15.0, 0.0, 15.0),
+            ],
+            min_spawn_distance: 8.0,
+            first_wave_delay: 2.0,
+            break_time: 5.0,
+            spawn_interval: 1.0,
+            first_wave_size: 1,
+            extra_enemies_per_wave: 2,
+            health_growth: 0.34,
+            speed_growth: 0.1,
+            accuracy_growth: 0.05,
+        }
+    }
+}
+
+impl WaveSettings {
+    /// Number of enemies in wave `wave`, counting from 1.
+    pub fn wave_size(&self, wave: u32) -> u32 {
+        self.first_wave_size + self.extra_enemies_per_wave * wave.saturating_sub(1)
+    }
+
+    /// How much tougher than their archetype the enemies in wave `wave` are, counting from 1.
+    pub fn difficulty(&self, wave: u32) -> Difficulty {
+        let waves_past_first = wave.saturating_sub(1) as f32;
+        Difficulty {
+            health: 1.0 + self.health_growth * waves_past_first,
+            speed: 1.0 + self.speed_growth * waves_past_first,
+            accuracy: self.accuracy_growth * waves_past_first,
+        }
+    }
+
+    /// A random spawn point out of the player's face, or the farthest one if all are close.
+    fn pick_spawn_point(&self, player: Vec3) -> Option<Vec3> {
+        let distant: Vec<Vec3> = self
+            .spawn_points
+            .iter()
+            .copied()
+            .filter(|point| point.xz().distance(player.xz()) >= self.min_spawn_distance)
+            .collect();
+        distant
+            .choose(&mut rand::thread_rng())
+            .copied()
+            .or_else(|| {
+                self.spawn_points.iter().copied().max_by(|a, b| {
+                    a.distance_squared(player)
+                        .total_cmp(&b.distance_squared(player))
+                })
+            })
+    }
+}
+
+/// What the wave director is waiting for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WavePhase {
+    /// Counting down to the next wave.
+    Break,
+    /// Bringing in the enemies of the current wave one by one.
+    Spawning,
+    /// Everyone is in, waiting for the player to clear the wave.
+    Fighting,
+}
+
+/// Progress through the waves, advanced by `run_waves`.
+#[derive(Debug, Clone, Resource)]
+pub struct WaveDirector {
+    /// Current wave, counting from 1, or 0 before the first one.
+    pub wave: u32,
+    pub phase: WavePhase,
+    /// Enemies of the current wave that still have to spawn.
+    pub left_to_spawn: u32,
+    /// Runs the break between waves, or the interval between spawns.
+    pub timer: Timer,
+}
+
+impl FromWorld for WaveDirector {
+    fn from_world(world: &mut World) -> Self {
+        let settings = world.get_resource_or_init::<WaveSettings>();
+        Self {
+            wave: 0,
+            phase: WavePhase::Break,
+            left_to_spawn: 0,
+            timer: Timer::from_seconds(settings.first_wave_delay, TimerMode::Once),
+        }
+    }
+}
+
+/// Tag for the text showing the wave and the enemies left in it.
+#[derive(Component)]
+pub struct WaveCounter;
+
+/// Starts waves, spawns their enemies over time and waits for them to be cleared.
+pub fn run_waves(
+    mut commands: Commands,
+    time: Res<Time>,
+    settings: Res<WaveSettings>,
+    mut director: ResMut<WaveDirector>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    enemy_archetypes: Res<EnemyArchetypes>,
+    folders: Res<Assets<LoadedFolder>>,
+    archetypes: Res<Assets<EnemyArchetype>>,
+    enemies: Query<(), (With<Enemy>, Without<Dying>)>,
+    player: Single<&Transform, With<Player>>,
+) {
+    director.timer.tick(time.delta());
+
+    match director.phase {
+        WavePhase::Break if director.timer.finished() => {
+            director.wave += 1;
+            director.left_to_spawn = settings.wave_size(director.wave);
+            director.phase = WavePhase::Spawning;
+            // Finished right away, so the first enemy spawns at once.
+            director.timer = Timer::from_seconds(0.0, TimerMode::Once);
+            info!(
+                "Wave {} with {} enemies",
+                director.wave, director.left_to_spawn
+            );
+        }
+        WavePhase::Spawning if director.timer.finished() => {
+            // Pick among the kinds unlocked by this wave, or any kind if none are yet.
+            let loaded: Vec<_> = enemy_archetypes.loaded(&folders, &archetypes).collect();
+            let unlocked: Vec<_> = loaded
+                .iter()
+                .filter(|(_, archetype)| archetype.first_wave <= director.wave)
+                .collect();
+            let picked = unlocked
+                .choose(&mut rand::thread_rng())
+                .copied()
+                .or_else(|| loaded.choose(&mut rand::thread_rng()));
+            // Hold the wave until the archetypes finished loading.
+            let Some((handle, archetype)) = picked else {
+                return;
+            };
+
+            if let Some(point) = settings.pick_spawn_point(player.translation) {
+                spawn_enemy(
+                    &mut commands,
+                    &mut meshes,
+                    &mut materials,
+                    handle.clone(),
+                    archetype,
+                    point,
+                    settings.difficulty(director.wave),
+                );
+            }
+            director.left_to_spawn = director.left_to_spawn.saturating_sub(1);
+            if director.left_to_spawn == 0 {
+                director.phase = WavePhase::Fighting;
+            } else {
+                director.timer = Timer::from_seconds(settings.spawn_interval, TimerMode::Once);
+            }
+        }
+        WavePhase::Fighting if enemies.is_empty() => {
+            info!("Wave {} cleared", director.wave);
+            director.phase = WavePhase::Break;
+            director.timer = Timer::from_seconds(settings.break_time, TimerMode::Once);
+        }
+        _ => {}
+    }
+}
+
+/// Spawns the wave counter at the top of the screen.
+pub fn spawn_wave_counter(mut commands: Commands) {
+    commands
+        .spawn(Node {
+            position_type: PositionType::Absolute,
+            top: Val::Px(12.0),
+            width: Val::Percent(100.0),
+            justify_content: JustifyContent::Center,
+            ..default()
+        })
+        .with_child((WaveCounter, Text::default()));
+}
+
+pub fn update_wave_counter(
+    director: Res<WaveDirector>,
+    enemies: Query<(), (With<Enemy>, Without<Dying>)>,
+    mut counter_query: Query<&mut Text, With<WaveCounter>>,
+) {
+    let remaining = enemies.iter().count() as u32 + director.left_to_spawn;
+
+    for mut text in &mut counter_query {
+        text.0 = match director.phase {
+            WavePhase::Break => format!(
+                "Wave {} in {:.0}",
+                director.wave + 1,
+                director.timer.remaining_secs().ceil()
+            ),
+            WavePhase::Spawning | WavePhase::Fighting => {
+                format!("Wave {}  |  {remaining} enemies left", director.wave)
+            }
+        };
+    }
+}
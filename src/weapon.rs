@@ -0,0 +1,514 @@
+use bevy::pbr::StandardMaterial;
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+
+use crate::aiming;
+use crate::ball::{Ball, BallBundle};
+use crate::damage::{DamageEvent, DamageRules, HitEvent, PROJECTILE_GROUP};
+use crate::entity::{Health, Owner, Player, Projectile, Team};
+use crate::input_map::Action;
+use crate::sights::AimDownSights;
+use crate::world_model::WorldModelCamera;
+
+/// Most colliders a single hitscan shot passes through, so a stack of thin walls can't stall it.
+const MAX_PENETRATIONS: u32 = 8;
+
+/// How far past the back of a collider a penetrating shot carries on, so it doesn't hit it again.
+const PENETRATION_EPSILON: f32 = 0.01;
+
+/// Seconds a tracer takes to fade away.
+const TRACER_LIFETIME: f32 = 0.1;
+
+/// Width of a tracer, in meters.
+const TRACER_WIDTH: f32 = 0.03;
+
+/// How the shots of a `Weapon` travel.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum FireMode {
+    /// Balls flying under gravity, which hit whatever they touch first.
+    #[default]
+    Projectile,
+    /// Rays that hit instantly and can pass through thin colliders.
+    Hitscan,
+}
+
+/// A gun described entirely by its stats, so a new weapon is just a new `Weapon` value.
+#[derive(Debug, Clone, Component)]
+pub struct Weapon {
+    pub fire_mode: FireMode,
+    /// Speed of a projectile leaving the muzzle, in meters per second.
+    pub projectile_speed: f32,
+    pub projectile_radius: f32,
+    /// Color of the projectiles, or of the tracers of hitscan shots.
+    pub projectile_color: Color,
+    /// Seconds a projectile lives before despawning.
+    pub projectile_lifetime: f32,
+    /// How far in front of the eyes projectiles spawn, so they don't start inside the shooter.
+    pub muzzle_offset: f32,
+    /// Shots per second.
+    pub fire_rate: f32,
+    /// Keep firing while the trigger is held instead of once per press.
+    pub automatic: bool,
+    /// Half-angle of the cone the projectiles scatter in, in radians.
+    pub spread: f32,
+    /// Fraction of the FOV left when aiming down the sights.
+    pub ads_zoom: f32,
+    /// Fraction of the spread left when aiming down the sights.
+    pub ads_spread: f32,
+    pub damage: i32,
+    /// Speed a hit adds to the target, in meters per second.
+    pub knockback: f32,
+    /// Projectiles fired at once, e.g. the pellets of a shotgun.
+    pub projectiles_per_shot: u32,
+    /// Farthest a hitscan shot reaches, in meters.
+    pub range: f32,
+    /// Distance up to which a hitscan shot deals full damage, in meters.
+    pub falloff_start: f32,
+    /// Fraction of the damage a hitscan shot still deals at the end of its range.
+    pub min_damage_fraction: f32,
+    /// Total thickness of colliders a hitscan shot can pass through, in meters.
+    pub penetration: f32,
+    /// Seconds until the weapon can fire again.
+    pub cooldown: f32,
+    /// Rounds a full magazine holds. Every shot takes one round, no matter how many projectiles it fires.
+    pub magazine_size: u32,
+    /// Rounds left in the magazine.
+    pub magazine: u32,
+    /// Rounds carried besides the magazine.
+    pub reserve: u32,
+    /// Most rounds that can be carried besides the magazine.
+    pub max_reserve: u32,
+    /// Seconds a reload takes.
+    pub reload_time: f32,
+    /// Seconds until the running reload completes, or `None` while not reloading.
+    pub reload: Option<f32>,
+}
+
+impl Default for Weapon {
+    /// The white ball launcher the player starts with.
+    fn default() -> Self {
+        Self {
+            fire_mode: FireMode::Projectile,
+            projectile_speed: 15.0,
+            projectile_radius: 0.3,
+            projectile_color: Color::WHITE,
+            projectile_lifetime: 5.0,
+            muzzle_offset: 1.0,
+            fire_rate: 5.0,
+            automatic: false,
+            spread: 0.0,
+            ads_zoom: 0.8,
+            ads_spread: 0.5,
+            damage: 1,
+            knockback: 3.0,
+            projectiles_per_shot: 1,
+            range: 100.0,
+            falloff_start: 100.0,
+            min_damage_fraction: 1.0,
+            penetration: 0.0,
+            cooldown: 0.0,
+            magazine_size: 12,
+            magazine: 12,
+            reserve: 48,
+            max_reserve: 48,
+            reload_time: 1.5,
+            reload: None,
+        }
+    }
+}
+
+impl Weapon {
+    /// An automatic hitscan rifle that loses damage over distance and shoots through thin cover.
+    pub fn rifle() -> Self {
+        Self {
+            fire_mode: FireMode::Hitscan,
+            projectile_color: Color::srgb(1.0, 0.9, 0.5),
+            muzzle_offset: 0.5,
+            fire_rate: 8.0,
+            automatic: true,
+            spread: 0.01,
+            ads_zoom: 0.5,
+            ads_spread: 0.25,
+            damage: 2,
+            knockback: 1.5,
+            range: 80.0,
+            falloff_start: 20.0,
+            min_damage_fraction: 0.5,
+            penetration: 0.6,
+            magazine_size: 30,
+            magazine: 30,
+            reserve: 90,
+            max_reserve: 90,
+            reload_time: 2.0,
+            ..default()
+        }
+    }
+
+    pub fn is_reloading(&self) -> bool {
+        self.reload.is_some()
+    }
+
+    /// Neither the magazine nor the reserve have any rounds left.
+    pub fn is_out_of_ammo(&self) -> bool {
+        self.magazine == 0 && self.reserve == 0
+    }
+
+    fn can_reload(&self) -> bool {
+        !self.is_reloading() && self.magazine < self.magazine_size && self.reserve > 0
+    }
+
+    fn start_reload(&mut self) {
+        self.reload = Some(self.reload_time);
+    }
+
+    /// Moves rounds from the reserve into the magazine once the reload time has passed.
+    fn tick_reload(&mut self, delta_secs: f32) {
+        let Some(remaining) = self.reload.as_mut() else {
+            return;
+        };
+        *remaining -= delta_secs;
+        if *remaining > 0.0 {
+            return;
+        }
+
+        let loaded = (self.magazine_size - self.magazine).min(self.reserve);
+        self.magazine += loaded;
+        self.reserve -= loaded;
+        self.reload = None;
+    }
+
+    /// Fills the magazine and the reserve and makes the weapon ready to fire.
+    pub fn refill(&mut self) {
+        self.magazine = self.magazine_size;
+        self.reserve = self.max_reserve;
+        self.reload = None;
+        self.cooldown = 0.0;
+    }
+
+    /// Damage of a hitscan shot after `distance` meters, dropping linearly from full damage
+    /// at `falloff_start` to `min_damage_fraction` of it at `range`. Never less than 1.
+    pub fn damage_at(&self, distance: f32) -> i32 {
+        let falloff = ((distance - self.falloff_start)
+            / (self.range - self.falloff_start).max(f32::EPSILON))
+        .clamp(0.0, 1.0);
+        let fraction = 1.0 + (self.min_damage_fraction - 1.0) * falloff;
+        (self.damage as f32 * fraction).round().max(1.0) as i32
+    }
+
+    /// Picks a random direction inside the spread cone around the `-Z` axis of `aim`,
+    /// which narrows the further the sights are raised.
+    fn scatter(&self, aim: Quat, sights: &AimDownSights) -> Dir3 {
+        let spread = self.spread * 1.0.lerp(self.ads_spread, sights.amount);
+        aiming::scatter(aim, spread)
+    }
+}
+
+/// Weapons the player carries besides the one in hand.
+#[derive(Debug, Default, Clone, Component)]
+pub struct Holster(pub Vec<Weapon>);
+
+/// Streak a hitscan shot leaves in the air, thinning out until it disappears.
+#[derive(Component)]
+pub struct Tracer {
+    pub timer: Timer,
+}
+
+/// Sent for every shot, so anyone within earshot can react to it.
+#[derive(Debug, Clone, Event)]
+pub struct GunshotEvent {
+    pub shooter: Entity,
+    /// Where the shot was fired from.
+    pub origin: Vec3,
+}
+
+/// Fires the player's weapon along the view, following its fire rate and spread,
+/// and handles reloading it.
+pub fn fire_weapon(
+    mut commands: Commands,
+    time: Res<Time>,
+    actions: Res<ButtonInput<Action>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    rules: Res<DamageRules>,
+    rapier_context: ReadRapierContext,
+    mut gunshots: EventWriter<GunshotEvent>,
+    (mut hits, mut damages): (EventWriter<HitEvent>, EventWriter<DamageEvent>),
+    player: Single<(Entity, &Team, &mut Weapon, &AimDownSights), With<Player>>,
+    camera: Single<&GlobalTransform, With<WorldModelCamera>>,
+    targets: Query<Option<&Team>, With<Health>>,
+) {
+    let (shooter, &team, mut weapon, sights) = player.into_inner();
+    // Only write when something changes, so `Changed<Weapon>` keeps meaning something.
+    if weapon.cooldown > 0.0 {
+        weapon.cooldown = (weapon.cooldown - time.delta_secs()).max(0.0);
+    }
+    if weapon.is_reloading() {
+        weapon.tick_reload(time.delta_secs());
+    }
+
+    if actions.just_pressed(Action::Reload) && weapon.can_reload() {
+        weapon.start_reload();
+    }
+
+    let trigger_pulled = if weapon.automatic {
+        actions.pressed(Action::Fire)
+    } else {
+        actions.just_pressed(Action::Fire)
+    };
+    if !trigger_pulled || weapon.cooldown > 0.0 {
+        return;
+    }
+
+    if weapon.magazine == 0 {
+        if weapon.can_reload() {
+            weapon.start_reload();
+        } else if weapon.is_out_of_ammo() && actions.just_pressed(Action::Fire) {
+            info!("Out of ammo");
+        }
+        return;
+    }
+
+    // Firing with rounds still in the magazine interrupts a reload, so a partly
+    // emptied magazine can be topped up without locking the player out of a fight.
+    weapon.reload = None;
+    weapon.magazine -= 1;
+    weapon.cooldown = 1.0 / weapon.fire_rate;
+
+    // The camera carries both the yaw of the player and the pitch of the head.
+    let (_, aim, eyes) = camera.to_scale_rotation_translation();
+    gunshots.write(GunshotEvent {
+        shooter,
+        origin: eyes,
+    });
+
+    match weapon.fire_mode {
+        FireMode::Projectile => {
+            // All projectiles of a shot share their assets.
+            let mesh = meshes.add(Sphere::new(weapon.projectile_radius));
+            let material = materials.add(weapon.projectile_color);
+
+            for _ in 0..weapon.projectiles_per_shot {
+                let direction = weapon.scatter(aim, sights);
+                // Spawn in front of the eyes, so the projectile doesn't start inside the player.
+                let translation = eyes + *camera.forward() * weapon.muzzle_offset;
+
+                commands.spawn((
+                    BallBundle {
+                        ball: Ball {
+                            lifetime: Timer::from_seconds(
+                                weapon.projectile_lifetime,
+                                TimerMode::Once,
+                            ),
+                        },
+                        rigid_body: RigidBody::Dynamic,
+                        mesh: Mesh3d(mesh.clone()),
+                        material: MeshMaterial3d(material.clone()),
+                        collider: Collider::ball(weapon.projectile_radius),
+                        restitution: Restitution::coefficient(0.8),
+                        friction: Friction::coefficient(5.0),
+                        damping: Damping {
+                            linear_damping: 0.5,
+                            angular_damping: 0.5,
+                        },
+                        transform: Transform::from_translation(translation),
+                        velocity: Velocity::linear(direction * weapon.projectile_speed),
+                    },
+                    Projectile {
+                        damage: weapon.damage,
+                        knockback: weapon.knockback,
+                    },
+                    Owner(shooter),
+                    team,
+                    rules.projectile_groups(team),
+                ));
+            }
+        }
+        FireMode::Hitscan => {
+            let Ok(context) = rapier_context.single() else {
+                return;
+            };
+            let material = materials.add(StandardMaterial {
+                base_color: weapon.projectile_color,
+                unlit: true,
+                ..default()
+            });
+
+            for _ in 0..weapon.projectiles_per_shot {
+                let direction = weapon.scatter(aim, sights);
+                let shot = HitscanShot {
+                    weapon: &weapon,
+                    shooter,
+                    team,
+                    origin: eyes,
+                    direction,
+                };
+                let end = trace_shot(&context, &rules, &shot, &targets, &mut hits, &mut damages);
+
+                // Start the tracer at the muzzle, so it doesn't cover the view.
+                let muzzle = eyes + direction * weapon.muzzle_offset;
+                let length = (end - eyes).length() - weapon.muzzle_offset;
+                if length <= 0.0 {
+                    continue;
+                }
+                commands.spawn((
+                    Tracer {
+                        timer: Timer::from_seconds(TRACER_LIFETIME, TimerMode::Once),
+                    },
+                    Mesh3d(meshes.add(Cuboid::new(TRACER_WIDTH, TRACER_WIDTH, length))),
+                    MeshMaterial3d(material.clone()),
+                    Transform::from_translation(muzzle + direction * length / 2.0)
+                        .looking_to(direction, Vec3::Y),
+                ));
+            }
+        }
+    }
+}
+
+/// A hitscan shot fired by `shooter`, see `trace_shot`.
+struct HitscanShot<'a> {
+    /// Weapon the shot came from, which sets its range, damage, knockback and penetration.
+    weapon: &'a Weapon,
+    shooter: Entity,
+    team: Team,
+    origin: Vec3,
+    direction: Dir3,
+}
+
+/// Casts `shot` from its origin along its direction and damages everything it hits
+/// on the way, passing through colliders as long as the weapon's penetration lasts.
+///
+/// Returns where the shot ended.
+fn trace_shot(
+    context: &RapierContext,
+    rules: &DamageRules,
+    shot: &HitscanShot,
+    targets: &Query<Option<&Team>, With<Health>>,
+    hits: &mut EventWriter<HitEvent>,
+    damages: &mut EventWriter<DamageEvent>,
+) -> Vec3 {
+    let &HitscanShot {
+        weapon,
+        shooter,
+        team,
+        origin,
+        direction,
+    } = shot;
+    // Projectiles flying by don't stop a shot.
+    let filter = QueryFilter::new()
+        .exclude_sensors()
+        .exclude_collider(shooter)
+        .groups(CollisionGroups::new(
+            Group::ALL,
+            Group::ALL - PROJECTILE_GROUP,
+        ));
+
+    let mut start = origin;
+    let mut travelled = 0.0;
+    let mut penetration = weapon.penetration;
+    for _ in 0..=MAX_PENETRATIONS {
+        let reach = weapon.range - travelled;
+        // Passing through the last collider used up what was left of the range.
+        if reach <= 0.0 {
+            break;
+        }
+        let Some((entity, distance)) = context.cast_ray(start, *direction, reach, true, filter)
+        else {
+            return start + direction * reach;
+        };
+        let hit = start + direction * distance;
+        travelled += distance;
+
+        if let Ok(target_team) = targets.get(entity)
+            && rules.allows(Some(shooter), Some(team), entity, target_team.copied())
+        {
+            hits.write(HitEvent {
+                target: entity,
+                source: Some(shooter),
+                position: hit,
+                impulse: direction * weapon.knockback,
+            });
+            damages.write(DamageEvent {
+                target: entity,
+                amount: weapon.damage_at(travelled),
+                source: Some(shooter),
+            });
+        }
+
+        // Measure the collider by casting back at it from as deep as the shot could still get.
+        // Starting inside of it means it's too thick, and the shot stops there.
+        let probe = hit + direction * penetration;
+        let only_hit = |other: Entity| other == entity;
+        let back = QueryFilter::new().predicate(&only_hit);
+        let thickness = match context.cast_ray(probe, -*direction, penetration, true, back) {
+            Some((_, from_probe)) if from_probe > 0.0 => penetration - from_probe,
+            _ => return hit,
+        };
+        penetration -= thickness;
+        start = hit + direction * (thickness + PENETRATION_EPSILON);
+        travelled += thickness + PENETRATION_EPSILON;
+    }
+    start
+}
+
+/// Swaps the weapon in hand for the next one in the holster.
+pub fn switch_weapon(
+    actions: Res<ButtonInput<Action>>,
+    player: Single<(&mut Weapon, &mut Holster), With<Player>>,
+) {
+    if !actions.just_pressed(Action::SwitchWeapon) {
+        return;
+    }
+    let (mut weapon, mut holster) = player.into_inner();
+    if holster.0.is_empty() {
+        return;
+    }
+
+    let next = holster.0.remove(0);
+    holster.0.push(std::mem::replace(&mut *weapon, next));
+}
+
+/// Thins out tracers over their lifetime and despawns them afterwards.
+pub fn fade_tracers(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut tracers: Query<(Entity, &mut Transform, &mut Tracer)>,
+) {
+    for (entity, mut transform, mut tracer) in &mut tracers {
+        tracer.timer.tick(time.delta());
+        let remaining = tracer.timer.fraction_remaining();
+        transform.scale = Vec3::new(remaining, remaining, 1.0);
+
+        if tracer.timer.finished() {
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn damage_falls_off_between_falloff_start_and_range() {
+        let weapon = Weapon {
+            damage: 8,
+            range: 80.0,
+            falloff_start: 20.0,
+            min_damage_fraction: 0.5,
+            ..default()
+        };
+
+        assert_eq!(weapon.damage_at(0.0), 8);
+        assert_eq!(weapon.damage_at(20.0), 8);
+        assert_eq!(weapon.damage_at(50.0), 6);
+        assert_eq!(weapon.damage_at(80.0), 4);
+        assert_eq!(weapon.damage_at(200.0), 4);
+    }
+
+    #[test]
+    fn rifle_deals_less_damage_far_away() {
+        let rifle = Weapon::rifle();
+
+        assert!(rifle.damage_at(rifle.range) < rifle.damage_at(rifle.falloff_start));
+    }
+}
@@ -3,7 +3,16 @@ use bevy::{
     prelude::*, render::view::RenderLayers,
 };
 use bevy_rapier3d::prelude::*;
+use crate::entity::Player;
+use crate::input_map::Action;
 use crate::render_layer::{DEFAULT_RENDER_LAYER, VIEW_MODEL_RENDER_LAYER};
+use crate::sights::AimDownSights;
+
+/// Narrowest FOV of the world model the player can pick, in degrees.
+pub const MIN_WORLD_FOV: f32 = 20.0;
+
+/// Widest FOV of the world model the player can pick, in degrees.
+pub const MAX_WORLD_FOV: f32 = 160.0;
 
 #[derive(Debug, Component)]
 pub struct WorldModelCamera;
@@ -77,22 +86,17 @@ pub fn spawn_text(mut commands: Commands) {
         )));
 }
 
+/// Changes the FOV of the world model the player picked. `aim_down_sights` zooms in from it.
 pub fn change_fov(
-    input: Res<ButtonInput<KeyCode>>,
-    mut world_model_projection: Single<&mut Projection, With<WorldModelCamera>>,
+    actions: Res<ButtonInput<Action>>,
+    mut sights: Single<&mut AimDownSights, With<Player>>,
 ) {
-    let Projection::Perspective(perspective) = world_model_projection.as_mut() else {
-        unreachable!(
-            "The `Projection` component was explicitly built with `Projection::Perspective`"
-        );
-    };
-
-    if input.pressed(KeyCode::ArrowUp) {
-        perspective.fov -= 1.0_f32.to_radians();
-        perspective.fov = perspective.fov.max(20.0_f32.to_radians());
+    if actions.pressed(Action::ZoomIn) {
+        sights.fov -= 1.0_f32.to_radians();
+        sights.fov = sights.fov.max(MIN_WORLD_FOV.to_radians());
     }
-    if input.pressed(KeyCode::ArrowDown) {
-        perspective.fov += 1.0_f32.to_radians();
-        perspective.fov = perspective.fov.min(160.0_f32.to_radians());
+    if actions.pressed(Action::ZoomOut) {
+        sights.fov += 1.0_f32.to_radians();
+        sights.fov = sights.fov.min(MAX_WORLD_FOV.to_radians());
     }
 }